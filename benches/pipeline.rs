@@ -0,0 +1,215 @@
+//! Per-stage timings of the crop pipeline (decode, trim, aspect crop, encode) on synthetic
+//! images, and the parallel throughput of `Pipeline::process_directory`.
+//!
+//! Save a baseline before a change and compare against it afterwards to catch regressions:
+//!
+//! ```text
+//! cargo bench --bench pipeline -- --save-baseline before
+//! cargo bench --bench pipeline -- --baseline before
+//! ```
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use image_cropper::{
+    crop_to_aspect_ratio, crop_transparent_edges, encode_image, Cropper, EncoderOptions, Pipeline,
+    DEFAULT_MAX_ASPECT, DEFAULT_MIN_ASPECT,
+};
+use std::fs;
+use std::hint::black_box;
+use std::time::Duration;
+
+const SIZES: [u32; 3] = [256, 1024, 4096];
+
+/// Where the opaque content of a synthetic image lies.
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// A block covering the middle half of each side.
+    Centered,
+    /// A small block near the top-left corner.
+    Corner,
+    /// No transparency at all.
+    Opaque,
+    /// Fully transparent.
+    Empty,
+    /// Scattered opaque pixels, so the trim cannot stop early.
+    Sparse,
+}
+
+impl Layout {
+    const ALL: [Layout; 5] = [
+        Layout::Centered,
+        Layout::Corner,
+        Layout::Opaque,
+        Layout::Empty,
+        Layout::Sparse,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Centered => "centered",
+            Layout::Corner => "corner",
+            Layout::Opaque => "opaque",
+            Layout::Empty => "empty",
+            Layout::Sparse => "sparse",
+        }
+    }
+
+    fn is_content(self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        match self {
+            Layout::Centered => {
+                (width / 4..width - width / 4).contains(&x)
+                    && (height / 4..height - height / 4).contains(&y)
+            }
+            Layout::Corner => {
+                (width / 16..width / 8).contains(&x) && (height / 16..height / 8).contains(&y)
+            }
+            Layout::Opaque => true,
+            Layout::Empty => false,
+            Layout::Sparse => hash(x, y).is_multiple_of(997),
+        }
+    }
+}
+
+/// A cheap deterministic pixel hash, so fixtures are identical between runs.
+fn hash(x: u32, y: u32) -> u32 {
+    let h = x.wrapping_mul(0x9e37_79b1) ^ y.wrapping_mul(0x85eb_ca77);
+    h ^ (h >> 15)
+}
+
+/// A `width` x `height` RGBA image with a gradient wherever `layout` has content.
+fn synthetic(width: u32, height: u32, layout: Layout) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if layout.is_content(x, y, width, height) {
+            let r = (x * 255 / width) as u8;
+            let g = (y * 255 / height) as u8;
+            Rgba([r, g, (hash(x, y) & 0x3f) as u8, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    }))
+}
+
+fn pixels(width: u32, height: u32) -> Throughput {
+    Throughput::Elements(u64::from(width) * u64::from(height))
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+    for size in SIZES {
+        let img = synthetic(size, size, Layout::Centered);
+        group.throughput(pixels(size, size));
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP] {
+            let bytes = encode_image(&img, format, &EncoderOptions::default()).unwrap();
+            let id = BenchmarkId::new(format!("{:?}", format).to_lowercase(), size);
+            group.bench_with_input(id, &bytes, |b, bytes| {
+                b.iter(|| image::load_from_memory_with_format(black_box(bytes), format).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_trim(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crop_transparent_edges");
+    group.sample_size(10);
+    for size in SIZES {
+        group.throughput(pixels(size, size));
+        for layout in Layout::ALL {
+            let img = synthetic(size, size, layout);
+            let id = BenchmarkId::new(layout.name(), size);
+            group.bench_with_input(id, &img, |b, img| {
+                b.iter(|| crop_transparent_edges(black_box(img), 0))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_aspect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crop_to_aspect_ratio");
+    group.sample_size(10);
+    for size in SIZES {
+        // 許容範囲内・横長すぎ・縦長すぎの 3 通り
+        for (shape, width, height) in [
+            ("within", size, size),
+            ("wide", size * 4, size / 2),
+            ("tall", size / 2, size * 4),
+        ] {
+            let img = synthetic(width, height, Layout::Opaque);
+            group.throughput(pixels(width, height));
+            let id = BenchmarkId::new(shape, size);
+            group.bench_with_input(id, &img, |b, img| {
+                b.iter_batched(
+                    || img.clone(),
+                    |img| crop_to_aspect_ratio(img, DEFAULT_MIN_ASPECT, DEFAULT_MAX_ASPECT),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.sample_size(10);
+    let options = EncoderOptions::default();
+    for size in SIZES {
+        let img = synthetic(size, size, Layout::Centered);
+        group.throughput(pixels(size, size));
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP] {
+            let id = BenchmarkId::new(format!("{:?}", format).to_lowercase(), size);
+            group.bench_with_input(id, &img, |b, img| {
+                b.iter(|| encode_image(black_box(img), format, &options).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Crops a directory of PNG sprites with an increasing number of worker threads.
+fn bench_process_directory(c: &mut Criterion) {
+    const FILES: usize = 32;
+    let dir = tempfile::tempdir().unwrap();
+    let (input_dir, output_dir) = (dir.path().join("in"), dir.path().join("out"));
+    fs::create_dir(&input_dir).unwrap();
+    for i in 0..FILES {
+        let layout = Layout::ALL[i % Layout::ALL.len()];
+        synthetic(512, 512, layout)
+            .save(input_dir.join(format!("sprite_{:02}.png", i)))
+            .unwrap();
+    }
+    let pipeline = Pipeline::new(Cropper::default());
+
+    let mut group = c.benchmark_group("process_directory");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(FILES as u64));
+    let max_threads = num_cpus::get();
+    let thread_counts = [1, 2, 4, 8].into_iter().filter(|&n| n < max_threads);
+    for threads in thread_counts.chain([max_threads]) {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_function(BenchmarkId::new("threads", threads), |b| {
+            b.iter(|| {
+                let reports =
+                    pool.install(|| pipeline.process_directory(&input_dir, &output_dir).unwrap());
+                assert!(reports.iter().all(|report| report.result.is_ok()));
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_decode,
+    bench_trim,
+    bench_aspect,
+    bench_encode,
+    bench_process_directory
+);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+//! Compares the raw-buffer alpha scan in `transparent_bounds` with a per-pixel
+//! `get_pixel` scan, the way `crop_transparent_edges` used to work.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use image_cropper::transparent_bounds;
+use std::hint::black_box;
+
+/// A square image with an opaque block covering the middle `fraction` of each side.
+fn sprite(size: u32, fraction: f32) -> DynamicImage {
+    let margin = (size as f32 * (1.0 - fraction) / 2.0) as u32;
+    let content = margin..size - margin;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(size, size, |x, y| {
+        if content.contains(&x) && content.contains(&y) {
+            Rgba([200, 120, 40, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    }))
+}
+
+/// The previous implementation: four edge scans through `DynamicImage::get_pixel`, with
+/// column-major left and right passes.
+fn get_pixel_bounds(img: &DynamicImage, alpha_threshold: u8) -> (u32, u32, u32, u32) {
+    let (width, height) = img.dimensions();
+    let is_content = |x, y| img.get_pixel(x, y)[3] > alpha_threshold;
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, width, height);
+    'outer: for y in 0..height {
+        for x in 0..width {
+            if is_content(x, y) {
+                top = y;
+                break 'outer;
+            }
+        }
+    }
+    'outer: for y in (0..height).rev() {
+        for x in 0..width {
+            if is_content(x, y) {
+                bottom = y + 1;
+                break 'outer;
+            }
+        }
+    }
+    'outer: for x in 0..width {
+        for y in top..bottom {
+            if is_content(x, y) {
+                left = x;
+                break 'outer;
+            }
+        }
+    }
+    'outer: for x in (0..width).rev() {
+        for y in top..bottom {
+            if is_content(x, y) {
+                right = x + 1;
+                break 'outer;
+            }
+        }
+    }
+    (left, top, right, bottom)
+}
+
+fn bench_transparent_bounds(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transparent_bounds");
+    group.sample_size(10);
+    let layouts = [("small sprite", 0.1), ("large sprite", 0.8), ("empty", 0.0)];
+    for size in [1024, 4096] {
+        for (layout, fraction) in layouts {
+            let img = sprite(size, fraction);
+            let id = format!("{size}px {layout}");
+            group.throughput(Throughput::Elements(u64::from(size) * u64::from(size)));
+            group.bench_with_input(BenchmarkId::new("raw buffer", &id), &img, |b, img| {
+                b.iter(|| transparent_bounds(black_box(img), 0))
+            });
+            group.bench_with_input(BenchmarkId::new("get_pixel", &id), &img, |b, img| {
+                b.iter(|| get_pixel_bounds(black_box(img), 0))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_transparent_bounds);
+criterion_main!(benches);
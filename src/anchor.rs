@@ -0,0 +1,117 @@
+use crate::error::Error;
+use image::{DynamicImage, GenericImageView};
+use std::str::FromStr;
+
+/// Where the aspect-ratio crop window is placed along the side being shortened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    /// Keep the middle of the image.
+    #[default]
+    Center,
+    /// Keep the top edge when height is reduced.
+    Top,
+    /// Keep the bottom edge when height is reduced.
+    Bottom,
+    /// Keep the left edge when width is reduced.
+    Left,
+    /// Keep the right edge when width is reduced.
+    Right,
+    /// Place the window where the image has the most content.
+    ///
+    /// Content is measured as opaque pixel mass for images with alpha and as edge energy
+    /// (luma gradient magnitude) for opaque images.
+    Auto,
+}
+
+impl FromStr for Anchor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "center" | "centre" => Ok(Anchor::Center),
+            "top" => Ok(Anchor::Top),
+            "bottom" => Ok(Anchor::Bottom),
+            "left" => Ok(Anchor::Left),
+            "right" => Ok(Anchor::Right),
+            "auto" => Ok(Anchor::Auto),
+            _ => Err(Error::Parse(format!(
+                "invalid anchor '{}', expected center, top, bottom, left, right or auto",
+                s
+            ))),
+        }
+    }
+}
+
+/// Axis along which the crop window slides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Anchor {
+    /// Returns the offset of a window of `window` pixels along `axis` of `img`, whose length
+    /// on that axis is `length`.
+    pub(crate) fn offset(self, img: &DynamicImage, axis: Axis, length: u32, window: u32) -> u32 {
+        let slack = length - window;
+        match (self, axis) {
+            (Anchor::Top, Axis::Vertical) | (Anchor::Left, Axis::Horizontal) => 0,
+            (Anchor::Bottom, Axis::Vertical) | (Anchor::Right, Axis::Horizontal) => slack,
+            (Anchor::Auto, _) if slack > 0 => densest_window(&energy_profile(img, axis), window),
+            _ => slack / 2,
+        }
+    }
+}
+
+/// Sums the content energy of every column (horizontal) or row (vertical) of `img`.
+fn energy_profile(img: &DynamicImage, axis: Axis) -> Vec<u64> {
+    let (width, height) = img.dimensions();
+    let len = match axis {
+        Axis::Horizontal => width,
+        Axis::Vertical => height,
+    };
+    let mut profile = vec![0u64; len as usize];
+    let mut add = |x: u32, y: u32, energy: u64| {
+        let i = match axis {
+            Axis::Horizontal => x,
+            Axis::Vertical => y,
+        };
+        profile[i as usize] += energy;
+    };
+
+    if img.color().has_alpha() {
+        let buf = img.to_luma_alpha8();
+        for (x, y, pixel) in buf.enumerate_pixels() {
+            add(x, y, u64::from(pixel[1]));
+        }
+    } else {
+        let buf = img.to_luma8();
+        for (x, y, pixel) in buf.enumerate_pixels() {
+            let luma = i32::from(pixel[0]);
+            let right = buf.get_pixel((x + 1).min(width - 1), y)[0];
+            let below = buf.get_pixel(x, (y + 1).min(height - 1))[0];
+            let gradient = (luma - i32::from(right)).abs() + (luma - i32::from(below)).abs();
+            add(x, y, gradient as u64);
+        }
+    }
+    profile
+}
+
+/// Returns the start of the `window`-long run of `profile` with the largest sum.
+///
+/// Ties keep the run closest to the centre so that flat images behave like [`Anchor::Center`].
+fn densest_window(profile: &[u64], window: u32) -> u32 {
+    let window = window as usize;
+    let slack = profile.len() - window;
+    let centre = slack / 2;
+    let mut sum: u64 = profile[..window].iter().sum();
+    let mut best: (u64, usize) = (sum, 0);
+    for start in 1..=slack {
+        sum = sum + profile[start + window - 1] - profile[start - 1];
+        let closer = start.abs_diff(centre) < best.1.abs_diff(centre);
+        if sum > best.0 || (sum == best.0 && closer) {
+            best = (sum, start);
+        }
+    }
+    best.1 as u32
+}
@@ -0,0 +1,224 @@
+use crate::anchor::{Anchor, Axis};
+use crate::error::Error;
+use image::GenericImageView;
+use std::fmt;
+use std::str::FromStr;
+
+/// Default minimum aspect ratio (2:5).
+pub const DEFAULT_MIN_ASPECT: f32 = 2.0 / 5.0;
+/// Default maximum aspect ratio (5:2).
+pub const DEFAULT_MAX_ASPECT: f32 = 5.0 / 2.0;
+
+/// A width / height ratio, parsed from `W:H` (e.g. `16:9`) or a decimal (e.g. `1.777`).
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct AspectRatio(pub f32);
+
+impl AspectRatio {
+    /// Returns the ratio as width divided by height.
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::Parse(format!(
+                "invalid aspect ratio '{}', expected W:H or a positive number",
+                s
+            ))
+        };
+        let ratio = match s.split_once(':') {
+            Some((w, h)) => {
+                let w: f32 = w.trim().parse().map_err(|_| invalid())?;
+                let h: f32 = h.trim().parse().map_err(|_| invalid())?;
+                w / h
+            }
+            None => s.trim().parse().map_err(|_| invalid())?,
+        };
+        if ratio.is_finite() && ratio > 0.0 {
+            Ok(AspectRatio(ratio))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Which aspect ratios an image is cropped to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectConstraint {
+    /// Leave the aspect ratio unchanged.
+    None,
+    /// Keep the aspect ratio within `min..=max`.
+    Range { min: f32, max: f32 },
+    /// Crop to exactly this aspect ratio.
+    Exact(f32),
+}
+
+impl AspectConstraint {
+    /// A [`AspectConstraint::Range`], failing if `min` is greater than `max`.
+    pub fn range(min: f32, max: f32) -> Result<Self, Error> {
+        let range = AspectConstraint::Range { min, max };
+        range.validate()?;
+        Ok(range)
+    }
+
+    /// Fails if the constraint is a range whose minimum is greater than its maximum, which no
+    /// image could satisfy.
+    pub fn validate(self) -> Result<(), Error> {
+        match self {
+            AspectConstraint::Range { min, max } if min > max => Err(Error::Parse(format!(
+                "invalid aspect range: minimum {} is greater than maximum {}",
+                min, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the allowed `(min, max)` range, or `None` if the aspect step is disabled.
+    pub fn bounds(self) -> Option<(f32, f32)> {
+        match self {
+            AspectConstraint::None => None,
+            AspectConstraint::Range { min, max } => Some((min, max)),
+            AspectConstraint::Exact(ratio) => Some((ratio, ratio)),
+        }
+    }
+}
+
+impl Default for AspectConstraint {
+    fn default() -> Self {
+        AspectConstraint::Range {
+            min: DEFAULT_MIN_ASPECT,
+            max: DEFAULT_MAX_ASPECT,
+        }
+    }
+}
+
+/// How an image outside the allowed aspect ratios is corrected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AspectMode {
+    /// Crop away part of the image.
+    #[default]
+    Crop,
+    /// Extend the canvas so that the whole image is kept.
+    Pad,
+}
+
+impl FromStr for AspectMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "crop" => Ok(AspectMode::Crop),
+            "pad" => Ok(AspectMode::Pad),
+            _ => Err(Error::Parse(format!(
+                "invalid aspect mode '{}', expected crop or pad",
+                s
+            ))),
+        }
+    }
+}
+
+/// Crops the centre of `img` so that its aspect ratio lies within `min_aspect..=max_aspect`.
+///
+/// See [`aspect_crop_size`] for how the window size is chosen.
+pub fn crop_to_aspect_ratio(
+    img: image::DynamicImage,
+    min_aspect: f32,
+    max_aspect: f32,
+) -> image::DynamicImage {
+    crop_to_aspect_ratio_anchored(img, min_aspect, max_aspect, Anchor::Center)
+}
+
+/// Like [`crop_to_aspect_ratio`], but places the crop window according to `anchor`.
+pub fn crop_to_aspect_ratio_anchored(
+    img: image::DynamicImage,
+    min_aspect: f32,
+    max_aspect: f32,
+    anchor: Anchor,
+) -> image::DynamicImage {
+    let (left, top, new_width, new_height) =
+        aspect_crop_window(&img, min_aspect, max_aspect, anchor);
+    if (new_width, new_height) == img.dimensions() {
+        return img;
+    }
+    img.crop_imm(left, top, new_width, new_height)
+}
+
+/// Returns the `(left, top, width, height)` window that [`crop_to_aspect_ratio_anchored`]
+/// keeps from `img`.
+pub fn aspect_crop_window(
+    img: &image::DynamicImage,
+    min_aspect: f32,
+    max_aspect: f32,
+    anchor: Anchor,
+) -> (u32, u32, u32, u32) {
+    let (width, height) = img.dimensions();
+    let (new_width, new_height) = aspect_crop_size(width, height, min_aspect, max_aspect);
+    let left = if new_width < width {
+        anchor.offset(img, Axis::Horizontal, width, new_width)
+    } else {
+        0
+    };
+    let top = if new_height < height {
+        anchor.offset(img, Axis::Vertical, height, new_height)
+    } else {
+        0
+    };
+    (left, top, new_width, new_height)
+}
+
+/// Returns the size of the window that [`crop_to_aspect_ratio`] cuts out of a
+/// `width` x `height` image.
+///
+/// Images that are too tall lose height and images that are too wide lose width, so the
+/// window always fits inside the original. When rounding to whole pixels cannot hit the
+/// range exactly, the size whose ratio is closest to it is used. Ratios within a relative
+/// error of 1e-6 of a bound count as inside it.
+pub fn aspect_crop_size(width: u32, height: u32, min_aspect: f32, max_aspect: f32) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (width, height);
+    }
+    let (w, h) = (f64::from(width), f64::from(height));
+    // f32 の比は 2:5 のような値でも丸め誤差を含むので、ごく僅かに範囲を広げて比較する
+    let min = f64::from(min_aspect) * (1.0 - RATIO_TOLERANCE);
+    let max = f64::from(max_aspect) * (1.0 + RATIO_TOLERANCE);
+    let aspect_ratio = w / h;
+
+    if aspect_ratio < min {
+        // アスペクト比が小さい場合（縦長）、幅を維持して高さを削る
+        let new_height = fit_length(w / min, height, |d| w / d, min, max);
+        (width, new_height)
+    } else if aspect_ratio > max {
+        // アスペクト比が大きい場合（横長）、高さを維持して幅を削る
+        let new_width = fit_length(h * max, width, |d| d / h, min, max);
+        (new_width, height)
+    } else {
+        (width, height)
+    }
+}
+
+/// Relative slack applied to the aspect bounds to absorb `f32` rounding.
+const RATIO_TOLERANCE: f64 = 1e-6;
+
+/// Rounds the ideal length of the shrinking side to whole pixels within `1..=limit`,
+/// preferring a length whose resulting ratio stays inside `min..=max`.
+fn fit_length(ideal: f64, limit: u32, ratio_of: impl Fn(f64) -> f64, min: f64, max: f64) -> u32 {
+    let outside = |length: f64| {
+        let ratio = ratio_of(length);
+        (min - ratio).max(ratio - max).max(0.0)
+    };
+    [ideal.floor(), ideal.ceil()]
+        .map(|length| length.clamp(1.0, f64::from(limit)))
+        .into_iter()
+        .min_by(|a, b| outside(*a).total_cmp(&outside(*b)))
+        .map_or(limit, |length| length as u32)
+}
@@ -0,0 +1,135 @@
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An opaque 8-bit sRGB color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::new(255, 255, 255);
+    pub const BLACK: Color = Color::new(0, 0, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Converts the color to CIE L*a*b* (D65 white point).
+    pub fn to_lab(self) -> [f32; 3] {
+        fn linearize(c: u8) -> f32 {
+            let c = f32::from(c) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        fn f(t: f32) -> f32 {
+            if t > 216.0 / 24389.0 {
+                t.cbrt()
+            } else {
+                (24389.0 / 27.0 * t + 16.0) / 116.0
+            }
+        }
+
+        let (r, g, b) = (linearize(self.r), linearize(self.g), linearize(self.b));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+}
+
+impl From<image::Rgb<u8>> for Color {
+    fn from(p: image::Rgb<u8>) -> Self {
+        Self::new(p[0], p[1], p[2])
+    }
+}
+
+impl From<Color> for image::Rgb<u8> {
+    fn from(c: Color) -> Self {
+        image::Rgb([c.r, c.g, c.b])
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Parses `#RRGGBB`, `#RGB` or the same without the leading `#`.
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Parse(format!("invalid color '{}', expected #RRGGBB", s));
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16);
+        let parsed = match hex.len() {
+            6 => (channel(0..2), channel(2..4), channel(4..6)),
+            3 => {
+                let short = |i: usize| channel(i..i + 1).map(|v| v * 17);
+                (short(0), short(1), short(2))
+            }
+            _ => return Err(invalid()),
+        };
+        match parsed {
+            (Ok(r), Ok(g), Ok(b)) => Ok(Self::new(r, g, b)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Color space in which the distance between two colors is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Euclidean distance between 8-bit RGB values.
+    Rgb,
+    /// CIE76 ΔE between L*a*b* values.
+    #[default]
+    Lab,
+}
+
+impl ColorSpace {
+    /// Returns the coordinates of `c` in this color space.
+    pub fn coordinates(self, c: Color) -> [f32; 3] {
+        match self {
+            ColorSpace::Rgb => [f32::from(c.r), f32::from(c.g), f32::from(c.b)],
+            ColorSpace::Lab => c.to_lab(),
+        }
+    }
+
+    /// Returns the distance between `a` and `b` in this color space.
+    pub fn distance(self, a: Color, b: Color) -> f32 {
+        euclidean(self.coordinates(a), self.coordinates(b))
+    }
+}
+
+pub(crate) fn euclidean(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (d0, d1, d2) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    (d0 * d0 + d1 * d1 + d2 * d2).sqrt()
+}
+
+impl FromStr for ColorSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rgb" => Ok(ColorSpace::Rgb),
+            "lab" => Ok(ColorSpace::Lab),
+            _ => Err(Error::Parse(format!(
+                "invalid color space '{}', expected rgb or lab",
+                s
+            ))),
+        }
+    }
+}
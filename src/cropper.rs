@@ -0,0 +1,423 @@
+use crate::anchor::Anchor;
+use crate::aspect::{aspect_crop_window, AspectConstraint, AspectMode};
+use crate::color::{Color, ColorSpace};
+use crate::error::{Error, Result};
+use crate::margin::{Margin, MarginMode};
+use crate::pad::{extract_area, pad_to_aspect_ratio, PadFill};
+use crate::trim::{background_bounds, transparent_bounds, OpaquePolicy, TrimMode};
+use image::{DynamicImage, GenericImageView};
+use serde::Serialize;
+
+/// Default background color tolerance, roughly a clearly visible ΔE in Lab.
+pub const DEFAULT_COLOR_TOLERANCE: f32 = 10.0;
+
+/// Largest canvas, in bytes, that a margin may grow an image to. Matches the default
+/// allocation limit of `image`'s decoders, so outputs are never larger than inputs may be.
+const MAX_CANVAS_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Trims and aspect-corrects images according to its configuration.
+///
+/// Build one with [`Cropper::builder`].
+#[derive(Debug, Clone)]
+pub struct Cropper {
+    trim_mode: TrimMode,
+    opaque_policy: OpaquePolicy,
+    alpha_threshold: u8,
+    background: Option<Color>,
+    color_tolerance: f32,
+    color_space: ColorSpace,
+    aspect: AspectConstraint,
+    aspect_mode: AspectMode,
+    anchor: Anchor,
+    pad_fill: PadFill,
+    margin: Margin,
+    margin_mode: MarginMode,
+}
+
+/// A rectangle in pixel coordinates. `right` and `bottom` are exclusive.
+///
+/// Coordinates are signed because a box may reach past the image, e.g. with
+/// [`MarginMode::Extend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CropBox {
+    pub left: i64,
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+}
+
+impl CropBox {
+    fn from_bounds((left, top, right, bottom): (u32, u32, u32, u32)) -> Self {
+        Self {
+            left: left.into(),
+            top: top.into(),
+            right: right.into(),
+            bottom: bottom.into(),
+        }
+    }
+
+    /// Width of the box in pixels, saturating at `u32::MAX`.
+    pub fn width(&self) -> u32 {
+        u32::try_from((self.right - self.left).max(0)).unwrap_or(u32::MAX)
+    }
+
+    /// Height of the box in pixels, saturating at `u32::MAX`.
+    pub fn height(&self) -> u32 {
+        u32::try_from((self.bottom - self.top).max(0)).unwrap_or(u32::MAX)
+    }
+
+    /// Returns the part of the box that lies inside a `width` x `height` image.
+    pub fn clamp_to(self, width: u32, height: u32) -> CropBox {
+        let (width, height) = (i64::from(width), i64::from(height));
+        let left = self.left.clamp(0, width);
+        let top = self.top.clamp(0, height);
+        CropBox {
+            left,
+            top,
+            right: self.right.clamp(left, width),
+            bottom: self.bottom.clamp(top, height),
+        }
+    }
+
+    /// Returns the smallest box containing both `self` and `other`.
+    pub fn union(self, other: CropBox) -> CropBox {
+        CropBox {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+/// What [`Cropper::crop_with_info`] did to an image.
+///
+/// Boxes are given in the coordinates of the original image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropInfo {
+    /// Size of the input image.
+    pub original_size: (u32, u32),
+    /// The trim that was actually applied. Differs from the configured mode for images
+    /// without alpha, see [`OpaquePolicy`].
+    pub trim_mode: TrimMode,
+    /// Area kept by the trim step; the whole image when nothing was trimmed.
+    pub trim: CropBox,
+    /// The trim box grown by the margin, if a margin was set.
+    pub margin: Option<CropBox>,
+    /// Area kept by the aspect crop, if the aspect step cropped the image.
+    pub aspect_crop: Option<CropBox>,
+    /// Size of the resulting image, including any padding.
+    pub final_size: (u32, u32),
+}
+
+impl CropInfo {
+    /// The area of the original image that the result was cut from, before any aspect
+    /// padding. It reaches past the image where the margin extended the canvas.
+    pub fn kept(&self) -> CropBox {
+        self.aspect_crop.or(self.margin).unwrap_or(self.trim)
+    }
+
+    /// Percentage of the original area that was cut away by trimming and aspect cropping.
+    /// Canvas added by margins or padding does not count.
+    pub fn removed_area_percent(&self) -> f64 {
+        let (width, height) = self.original_size;
+        let original = u64::from(width) * u64::from(height);
+        if original == 0 {
+            return 0.0;
+        }
+        let kept = self.kept().clamp_to(width, height);
+        let kept = u64::from(kept.width()) * u64::from(kept.height());
+        (original - kept) as f64 / original as f64 * 100.0
+    }
+
+    /// Position of the result's top-left corner in the original image.
+    ///
+    /// Negative where an extended margin or [`AspectMode::Pad`] added canvas before the
+    /// original content.
+    pub fn source_offset(&self) -> (i64, i64) {
+        let kept = self.kept();
+        // パディングは内容を中央に置くので pad_buffer と同じ式で余白を求める
+        let pad_x = i64::from(self.final_size.0.saturating_sub(kept.width()) / 2);
+        let pad_y = i64::from(self.final_size.1.saturating_sub(kept.height()) / 2);
+        (kept.left - pad_x, kept.top - pad_y)
+    }
+}
+
+impl Cropper {
+    /// Returns a builder initialised with the default settings.
+    pub fn builder() -> CropperBuilder {
+        CropperBuilder::default()
+    }
+
+    /// Returns the aspect ratio constraint.
+    pub fn aspect(&self) -> AspectConstraint {
+        self.aspect
+    }
+
+    /// Applies the configured trim and aspect correction to `img`.
+    ///
+    /// Fails if an extended margin would grow the image past the canvas size limit.
+    pub fn crop(&self, img: &DynamicImage) -> Result<DynamicImage> {
+        Ok(self.crop_with_info(img)?.0)
+    }
+
+    /// Like [`Cropper::crop`], and also reports where the result came from.
+    pub fn crop_with_info(&self, img: &DynamicImage) -> Result<(DynamicImage, CropInfo)> {
+        self.crop_with_trim(img, self.trim_box(img))
+    }
+
+    /// Returns the trim that applies to `img`: the configured mode, unless alpha trimming
+    /// meets an image without alpha, which is handled according to the [`OpaquePolicy`].
+    pub fn effective_trim_mode(&self, img: &DynamicImage) -> TrimMode {
+        match (self.trim_mode, self.opaque_policy) {
+            // アルファのない画像は全画素が不透明なので走査しても何も削れない
+            (TrimMode::Alpha, OpaquePolicy::Skip) if !img.color().has_alpha() => TrimMode::None,
+            (TrimMode::Alpha, OpaquePolicy::Background) if !img.color().has_alpha() => {
+                TrimMode::Background
+            }
+            (mode, _) => mode,
+        }
+    }
+
+    /// Returns the area of `img` that the trim step keeps.
+    pub fn trim_box(&self, img: &DynamicImage) -> CropBox {
+        let (width, height) = img.dimensions();
+        CropBox::from_bounds(match self.effective_trim_mode(img) {
+            TrimMode::None => (0, 0, width, height),
+            TrimMode::Alpha => transparent_bounds(img, self.alpha_threshold),
+            TrimMode::Background => {
+                background_bounds(img, self.background, self.color_tolerance, self.color_space)
+            }
+        })
+    }
+
+    /// Like [`Cropper::crop_with_info`], but keeps `trim` instead of detecting the trim box.
+    ///
+    /// `trim` must lie inside `img`. The margin and aspect correction are applied as usual.
+    pub fn crop_with_trim(
+        &self,
+        img: &DynamicImage,
+        trim: CropBox,
+    ) -> Result<(DynamicImage, CropInfo)> {
+        let (width, height) = img.dimensions();
+        let margin = (self.margin != Margin::default())
+            .then(|| self.margin.expand(trim, width, height, self.margin_mode));
+        let kept = margin.unwrap_or(trim);
+        let trimmed = if kept == CropBox::from_bounds((0, 0, width, height)) {
+            img.clone()
+        } else {
+            extract(img, kept)?
+        };
+
+        let (cropped, aspect_crop) = match self.aspect.bounds() {
+            Some((min_aspect, max_aspect)) => match self.aspect_mode {
+                AspectMode::Crop => {
+                    let (left, top, new_width, new_height) =
+                        aspect_crop_window(&trimmed, min_aspect, max_aspect, self.anchor);
+                    if (new_width, new_height) == trimmed.dimensions() {
+                        (trimmed, None)
+                    } else {
+                        // 報告する枠は元画像の座標に直す
+                        let window = CropBox {
+                            left: kept.left + i64::from(left),
+                            top: kept.top + i64::from(top),
+                            right: kept.left + i64::from(left + new_width),
+                            bottom: kept.top + i64::from(top + new_height),
+                        };
+                        (
+                            trimmed.crop_imm(left, top, new_width, new_height),
+                            Some(window),
+                        )
+                    }
+                }
+                AspectMode::Pad => (
+                    pad_to_aspect_ratio(trimmed, min_aspect, max_aspect, self.pad_fill),
+                    None,
+                ),
+            },
+            None => (trimmed, None),
+        };
+
+        let info = CropInfo {
+            original_size: (width, height),
+            trim_mode: self.effective_trim_mode(img),
+            trim,
+            margin,
+            aspect_crop,
+            final_size: cropped.dimensions(),
+        };
+        Ok((cropped, info))
+    }
+
+    /// Crops `img` exactly as recorded in `plan`, typically taken from another frame of the
+    /// same animation, so that all frames share one rectangle.
+    ///
+    /// Fails if `img` does not have the plan's original size.
+    pub fn apply(&self, img: &DynamicImage, plan: &CropInfo) -> Result<DynamicImage> {
+        let (width, height) = img.dimensions();
+        if (width, height) != plan.original_size {
+            return Err(Error::Unsupported(format!(
+                "image is {}x{} but its group is {}x{}",
+                width, height, plan.original_size.0, plan.original_size.1
+            )));
+        }
+        let cropped = extract(img, plan.kept())?;
+        Ok(match (self.aspect.bounds(), self.aspect_mode) {
+            (Some((min_aspect, max_aspect)), AspectMode::Pad) => {
+                pad_to_aspect_ratio(cropped, min_aspect, max_aspect, self.pad_fill)
+            }
+            _ => cropped,
+        })
+    }
+}
+
+/// Cuts `area` out of `img`, filling any part outside the image with transparency.
+///
+/// Fails if `area` is too large to allocate, which only an extended margin can cause.
+fn extract(img: &DynamicImage, area: CropBox) -> Result<DynamicImage> {
+    let (width, height) = (area.right - area.left, area.bottom - area.top);
+    let color = img.color();
+    // extract_area は RGBA に揃えるので、チャンネルのバイト数の 4 倍になる
+    let bytes_per_pixel = u64::from(color.bytes_per_pixel() / color.channel_count()) * 4;
+    let bytes = u32::try_from(width)
+        .ok()
+        .zip(u32::try_from(height).ok())
+        .and_then(|(w, h)| u64::from(w).checked_mul(u64::from(h)))
+        .and_then(|pixels| pixels.checked_mul(bytes_per_pixel));
+    if bytes.is_none_or(|bytes| bytes > MAX_CANVAS_BYTES) {
+        return Err(Error::CanvasTooLarge(width, height));
+    }
+    Ok(extract_area(
+        img,
+        area.left,
+        area.top,
+        area.width(),
+        area.height(),
+    ))
+}
+
+impl Default for Cropper {
+    fn default() -> Self {
+        Self {
+            trim_mode: TrimMode::Alpha,
+            opaque_policy: OpaquePolicy::Skip,
+            alpha_threshold: 0,
+            background: None,
+            color_tolerance: DEFAULT_COLOR_TOLERANCE,
+            color_space: ColorSpace::Lab,
+            aspect: AspectConstraint::default(),
+            aspect_mode: AspectMode::Crop,
+            anchor: Anchor::Center,
+            pad_fill: PadFill::Transparent,
+            margin: Margin::default(),
+            margin_mode: MarginMode::Clamp,
+        }
+    }
+}
+
+/// Builder for [`Cropper`].
+#[derive(Debug, Clone, Default)]
+pub struct CropperBuilder {
+    cropper: Cropper,
+}
+
+impl CropperBuilder {
+    /// Sets what is trimmed from the edges. Defaults to [`TrimMode::Alpha`].
+    pub fn trim_mode(mut self, trim_mode: TrimMode) -> Self {
+        self.cropper.trim_mode = trim_mode;
+        self
+    }
+
+    /// Sets what [`TrimMode::Alpha`] does with images without an alpha channel.
+    /// Defaults to [`OpaquePolicy::Skip`].
+    pub fn opaque_policy(mut self, opaque_policy: OpaquePolicy) -> Self {
+        self.cropper.opaque_policy = opaque_policy;
+        self
+    }
+
+    /// Sets the alpha value at or below which a pixel counts as transparent when trimming.
+    ///
+    /// The value is on the 8-bit scale and applies to 16-bit and float images
+    /// proportionally. Defaults to 0, so only fully transparent pixels are trimmed.
+    pub fn alpha_threshold(mut self, alpha_threshold: u8) -> Self {
+        self.cropper.alpha_threshold = alpha_threshold;
+        self
+    }
+
+    /// Sets the background color removed by [`TrimMode::Background`].
+    ///
+    /// `None`, the default, detects the background from the image corners.
+    pub fn background(mut self, background: Option<Color>) -> Self {
+        self.cropper.background = background;
+        self
+    }
+
+    /// Sets how far a pixel may be from the background color and still be trimmed.
+    ///
+    /// The distance is measured in the color space set by [`CropperBuilder::color_space`].
+    /// Defaults to [`DEFAULT_COLOR_TOLERANCE`].
+    pub fn color_tolerance(mut self, color_tolerance: f32) -> Self {
+        self.cropper.color_tolerance = color_tolerance;
+        self
+    }
+
+    /// Sets the color space used to compare pixels with the background. Defaults to Lab.
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.cropper.color_space = color_space;
+        self
+    }
+
+    /// Sets the aspect ratio constraint. Defaults to a 2:5..=5:2 range.
+    pub fn aspect(mut self, aspect: AspectConstraint) -> Self {
+        self.cropper.aspect = aspect;
+        self
+    }
+
+    /// Shorthand for [`AspectConstraint::Range`] with the given width / height bounds.
+    ///
+    /// The bounds are not checked here; see [`AspectConstraint::validate`].
+    pub fn aspect_range(self, min_aspect: f32, max_aspect: f32) -> Self {
+        self.aspect(AspectConstraint::Range {
+            min: min_aspect,
+            max: max_aspect,
+        })
+    }
+
+    /// Sets whether images are cropped or padded to fit the aspect constraint.
+    /// Defaults to [`AspectMode::Crop`].
+    pub fn aspect_mode(mut self, aspect_mode: AspectMode) -> Self {
+        self.cropper.aspect_mode = aspect_mode;
+        self
+    }
+
+    /// Sets where the [`AspectMode::Crop`] window is placed. Defaults to [`Anchor::Center`].
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.cropper.anchor = anchor;
+        self
+    }
+
+    /// Sets how the area added by [`AspectMode::Pad`] is filled. Defaults to transparent.
+    pub fn pad_fill(mut self, pad_fill: PadFill) -> Self {
+        self.cropper.pad_fill = pad_fill;
+        self
+    }
+
+    /// Sets the border kept around the trimmed content, before the aspect correction.
+    /// Defaults to no margin.
+    pub fn margin(mut self, margin: Margin) -> Self {
+        self.cropper.margin = margin;
+        self
+    }
+
+    /// Sets what happens when the margin reaches past the image. Defaults to
+    /// [`MarginMode::Clamp`].
+    pub fn margin_mode(mut self, margin_mode: MarginMode) -> Self {
+        self.cropper.margin_mode = margin_mode;
+        self
+    }
+
+    /// Builds the configured [`Cropper`].
+    pub fn build(self) -> Cropper {
+        self.cropper
+    }
+}
@@ -0,0 +1,181 @@
+use crate::error::{Error, Result};
+use glob::Pattern;
+use image::ImageFormat;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Include/exclude glob filters applied to paths relative to the input directory.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let parse = |patterns: &[String]| -> Result<Vec<Pattern>> {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p).map_err(Into::into))
+                .collect()
+        };
+        Ok(Self {
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+        })
+    }
+
+    /// Whether `relative` passes the filters. With no include patterns everything is included.
+    pub(crate) fn matches(&self, relative: &Path) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|p| p.matches_path(relative));
+        included && !self.exclude.iter().any(|p| p.matches_path(relative))
+    }
+}
+
+/// Lists the decodable image files inside `input_dir` that pass `filter`, sorted by path.
+///
+/// Paths are returned relative to `input_dir`. With `recursive` set, subdirectories are
+/// walked too, except for `skip_dir` (typically the output directory) and symlinked
+/// directories. Symlinked files are followed like regular files.
+pub(crate) fn discover_images(
+    input_dir: &Path,
+    filter: &FileFilter,
+    recursive: bool,
+    skip_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
+    // 出力先が入力ツリーの中にある場合（既定の input/output）に再処理しないよう除外する
+    let skip_dir = skip_dir.and_then(|dir| dir.canonicalize().ok());
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative_dir) = pending.pop() {
+        for entry in std::fs::read_dir(input_dir.join(&relative_dir))? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let relative = relative_dir.join(entry.file_name());
+            if file_type.is_dir() {
+                let skipped = skip_dir
+                    .as_deref()
+                    .is_some_and(|skip| entry.path().canonicalize().is_ok_and(|dir| dir == skip));
+                if recursive && !skipped {
+                    pending.push(relative);
+                }
+            } else if is_file(&entry.path(), file_type)
+                && !is_backup_or_temp(&relative)
+                && filter.matches(&relative)
+                && is_image_file(&entry.path())
+            {
+                files.push(relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Lists the decodable image files matched by the glob `pattern` that pass `filter`,
+/// sorted by path.
+///
+/// Paths are returned relative to [`glob_base`] of the pattern, which is also what the
+/// filters are matched against. Files inside `skip_dir` are left out.
+pub(crate) fn expand_glob(
+    pattern: &Path,
+    filter: &FileFilter,
+    skip_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
+    let pattern_str = pattern
+        .to_str()
+        .ok_or_else(|| Error::InvalidPath(pattern.to_path_buf()))?;
+    let base = glob_base(pattern);
+    let skip_dir = skip_dir.and_then(|dir| dir.canonicalize().ok());
+    let mut matched = false;
+    let mut files = Vec::new();
+    for entry in glob::glob(pattern_str)? {
+        // GlobError::into_error は新しい glob で非推奨なので io::Error を作り直す
+        let path = entry.map_err(|e| std::io::Error::new(e.error().kind(), e.to_string()))?;
+        matched = true;
+        let skipped = skip_dir
+            .as_deref()
+            .is_some_and(|skip| path.canonicalize().is_ok_and(|p| p.starts_with(skip)));
+        let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+        if path.is_file()
+            && !skipped
+            && !is_backup_or_temp(&path)
+            && filter.matches(&relative)
+            && is_image_file(&path)
+        {
+            files.push(relative);
+        }
+    }
+    if !matched {
+        return Err(Error::NotFound(pattern.to_path_buf()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether `path` contains glob metacharacters.
+pub(crate) fn is_glob(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.contains(['*', '?', '[']))
+}
+
+/// Returns the leading directories of a glob pattern that contain no metacharacters,
+/// or `.` if the pattern starts with one.
+pub(crate) fn glob_base(pattern: &Path) -> PathBuf {
+    let base: PathBuf = pattern
+        .components()
+        .take_while(|c| !is_glob(Path::new(c.as_os_str())))
+        .collect();
+    if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    }
+}
+
+/// Whether the entry at `path` is a file, following a symlink to its target.
+fn is_file(path: &Path, file_type: std::fs::FileType) -> bool {
+    if file_type.is_symlink() {
+        // DirEntry::file_type はリンクを辿らないので、リンク先の種類を調べ直す
+        return std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file());
+    }
+    file_type.is_file()
+}
+
+/// Whether `path` is a `.bak` copy or an unfinished temporary file left by an atomic write.
+///
+/// Both usually still sniff as images, so they would otherwise be cropped on the next run.
+fn is_backup_or_temp(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("bak") | Some("tmp")
+    )
+}
+
+/// Whether `path` looks like an image the `image` crate can decode.
+///
+/// The extension is checked first; files with an unknown or missing extension are sniffed
+/// from their leading bytes. AVIF files are not picked up: this build can write AVIF but
+/// has no AVIF decoder.
+pub fn is_image_file(path: &Path) -> bool {
+    match ImageFormat::from_path(path) {
+        Ok(format) => can_decode(format),
+        Err(_) => sniff_format(path).is_some_and(can_decode),
+    }
+}
+
+fn can_decode(format: ImageFormat) -> bool {
+    // image の avif フィーチャはエンコーダだけだが reading_enabled は true を返す
+    format.reading_enabled() && format != ImageFormat::Avif
+}
+
+fn sniff_format(path: &Path) -> Option<ImageFormat> {
+    let mut header = Vec::with_capacity(32);
+    File::open(path)
+        .ok()?
+        .take(32)
+        .read_to_end(&mut header)
+        .ok()?;
+    image::guess_format(&header).ok()
+}
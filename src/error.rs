@@ -0,0 +1,101 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Errors returned by the cropping library.
+#[derive(Debug)]
+pub enum Error {
+    /// Decoding or encoding an image failed.
+    Image(image::ImageError),
+    /// An encoder outside the `image` crate failed.
+    Encode(String),
+    /// A filesystem operation failed.
+    Io(std::io::Error),
+    /// A glob pattern could not be parsed.
+    Pattern(glob::PatternError),
+    /// An input path does not exist.
+    NotFound(PathBuf),
+    /// A path could not be used as an input or output location.
+    InvalidPath(PathBuf),
+    /// Another input of the batch, the second path, would be written to the same output,
+    /// the first path.
+    DuplicateOutput(PathBuf, PathBuf),
+    /// The output path is the input file itself, which only in-place mode may overwrite.
+    OutputIsInput(PathBuf),
+    /// Cropping would produce a canvas of this width and height, which is too large to
+    /// allocate.
+    CanvasTooLarge(i64, i64),
+    /// An option value could not be parsed.
+    Parse(String),
+    /// The requested operation is not available in this build.
+    Unsupported(String),
+}
+
+/// Result type used throughout the library.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Image(e) => write!(f, "image error: {}", e),
+            Error::Encode(msg) => write!(f, "encoding failed: {}", msg),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Pattern(e) => write!(f, "invalid glob pattern: {}", e),
+            Error::NotFound(path) => write!(f, "no such file or directory: {}", path.display()),
+            Error::InvalidPath(path) => write!(f, "invalid path: {}", path.display()),
+            Error::DuplicateOutput(output, other) => write!(
+                f,
+                "output {} would also be written for {}",
+                output.display(),
+                other.display()
+            ),
+            Error::OutputIsInput(path) => write!(
+                f,
+                "output {} is the input file; use in-place mode to overwrite it",
+                path.display()
+            ),
+            Error::CanvasTooLarge(width, height) => write!(
+                f,
+                "the margin would grow the image to {}x{} pixels, which is too large",
+                width, height
+            ),
+            Error::Parse(msg) => f.write_str(msg),
+            Error::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Pattern(e) => Some(e),
+            Error::Encode(_)
+            | Error::NotFound(_)
+            | Error::InvalidPath(_)
+            | Error::DuplicateOutput(..)
+            | Error::OutputIsInput(_)
+            | Error::CanvasTooLarge(..)
+            | Error::Parse(_)
+            | Error::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<glob::PatternError> for Error {
+    fn from(e: glob::PatternError) -> Self {
+        Error::Pattern(e)
+    }
+}
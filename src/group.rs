@@ -0,0 +1,92 @@
+use crate::error::{Error, Result};
+use glob::Pattern;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How files in a batch are grouped so that every member gets the same crop rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// Crop every file on its own.
+    #[default]
+    None,
+    /// Treat the whole batch as one group.
+    All,
+    /// Group files that are in the same directory.
+    Directory,
+    /// Group numbered frames such as `walk_001.png`, `walk_002.png` by the name left
+    /// after stripping the trailing frame number, within each directory.
+    Sequence,
+}
+
+impl FromStr for GroupBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(GroupBy::None),
+            "all" => Ok(GroupBy::All),
+            "directory" | "dir" => Ok(GroupBy::Directory),
+            "sequence" => Ok(GroupBy::Sequence),
+            _ => Err(Error::Parse(format!(
+                "invalid grouping '{}', expected none, all, directory or sequence",
+                s
+            ))),
+        }
+    }
+}
+
+/// Identifies the group a file belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum GroupKey {
+    Pattern(usize, PathBuf),
+    All,
+    Directory(PathBuf),
+    Sequence(PathBuf, String),
+}
+
+/// Group settings of a pipeline: explicit file name patterns, then a fallback rule.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Grouping {
+    patterns: Vec<Pattern>,
+    group_by: GroupBy,
+}
+
+impl Grouping {
+    pub(crate) fn new(patterns: &[String], group_by: GroupBy) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::new(p).map_err(Into::into))
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns, group_by })
+    }
+
+    /// Whether any file can end up in a group.
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.patterns.is_empty() || self.group_by != GroupBy::None
+    }
+
+    /// Returns the group of `path`, or `None` if it is cropped on its own.
+    ///
+    /// A file whose name matches one of the patterns joins the group of the first matching
+    /// pattern within its directory; other files are grouped by [`GroupBy`].
+    pub(crate) fn key(&self, path: &Path) -> Option<GroupKey> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let name = path.file_name().map(Path::new).unwrap_or(path);
+        if let Some(i) = self.patterns.iter().position(|p| p.matches_path(name)) {
+            return Some(GroupKey::Pattern(i, dir));
+        }
+        match self.group_by {
+            GroupBy::None => None,
+            GroupBy::All => Some(GroupKey::All),
+            GroupBy::Directory => Some(GroupKey::Directory(dir)),
+            GroupBy::Sequence => {
+                let stem = path.file_stem()?.to_str()?;
+                // 末尾のフレーム番号と区切り文字を取り除いたものを系列名とする
+                let name = stem
+                    .trim_end_matches(|c: char| c.is_ascii_digit())
+                    .trim_end_matches(['_', '-', '.', ' ']);
+                Some(GroupKey::Sequence(dir, name.to_string()))
+            }
+        }
+    }
+}
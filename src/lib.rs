@@ -0,0 +1,51 @@
+//! Trims transparent or solid-color edges from images and keeps their aspect ratio within bounds.
+//!
+//! ```no_run
+//! use image_cropper::{Cropper, Pipeline};
+//! use std::path::Path;
+//!
+//! let cropper = Cropper::builder().aspect_range(1.0, 16.0 / 9.0).build();
+//! let pipeline = Pipeline::new(cropper);
+//! pipeline.process_file(Path::new("sprite.png"), Path::new("out"))?;
+//! # Ok::<(), image_cropper::Error>(())
+//! ```
+
+mod anchor;
+mod aspect;
+mod color;
+mod cropper;
+mod discover;
+mod error;
+mod group;
+mod margin;
+mod naming;
+mod output;
+mod pad;
+mod pipeline;
+mod report;
+mod sidecar;
+mod trim;
+mod write;
+
+pub use anchor::Anchor;
+pub use aspect::{
+    aspect_crop_size, aspect_crop_window, crop_to_aspect_ratio, crop_to_aspect_ratio_anchored,
+    AspectConstraint, AspectMode, AspectRatio, DEFAULT_MAX_ASPECT, DEFAULT_MIN_ASPECT,
+};
+pub use color::{Color, ColorSpace};
+pub use cropper::{CropBox, CropInfo, Cropper, CropperBuilder, DEFAULT_COLOR_TOLERANCE};
+pub use discover::is_image_file;
+pub use error::{Error, Result};
+pub use group::GroupBy;
+pub use margin::{Length, Margin, MarginMode};
+pub use naming::{NameContext, NameTemplate, DEFAULT_NAME_TEMPLATE};
+pub use output::{encode_image, flatten, EncoderOptions, OutputFormat, PngCompression, PngFilter};
+pub use pad::{pad_to_aspect_ratio, PadFill};
+pub use pipeline::{default_output_dir, FileReport, Outcome, Pipeline, PipelineBuilder};
+pub use report::{write_report, ReportFormat, Summary};
+pub use sidecar::{write_manifest, SidecarFormat};
+pub use trim::{
+    background_bounds, crop_background_edges, crop_transparent_edges, detect_background,
+    transparent_bounds, OpaquePolicy, TrimMode,
+};
+pub use write::OverwritePolicy;
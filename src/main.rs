@@ -1,143 +1,457 @@
 use clap::Parser;
-use glob::glob;
-use image::GenericImageView;
-use rayon::prelude::*;
+use image_cropper::{
+    write_manifest, write_report, Anchor, AspectConstraint, AspectMode, AspectRatio, Color,
+    ColorSpace, Cropper, EncoderOptions, GroupBy, Margin, MarginMode, NameTemplate, OpaquePolicy,
+    OutputFormat, OverwritePolicy, PadFill, Pipeline, PngCompression, PngFilter, ReportFormat,
+    SidecarFormat, Summary, TrimMode, DEFAULT_COLOR_TOLERANCE, DEFAULT_MAX_ASPECT,
+    DEFAULT_MIN_ASPECT, DEFAULT_NAME_TEMPLATE,
+};
 use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::process::ExitCode;
 
 #[derive(Debug, Parser)]
 #[command(version, about = "A simple image cropping tool")]
 struct CliOptions {
-    /// Input image file or directory path.
-    #[arg(long, short)]
-    input_path: PathBuf,
+    /// Input image files, directories or glob patterns such as 'assets/**/*.png'. Repeatable.
+    /// A single '-' reads one image from stdin.
+    #[arg(long, short, num_args = 1.., required_unless_present = "files_from")]
+    input_path: Vec<PathBuf>,
+
+    /// Read more input paths from this file, or from stdin with '-'. Paths are separated by
+    /// newlines, or by NUL bytes as written by `find -print0`.
+    #[arg(long)]
+    files_from: Option<PathBuf>,
 
-    /// Output directory path.
+    /// Output directory path, or '-' to write a single image to stdout. Defaults to
+    /// `<input>/output` for a directory and to an `output` directory next to a single file.
     #[arg(long, short)]
     output_path: Option<PathBuf>,
 
+    /// Exact output file path for a single input file. The format follows its extension
+    /// unless --format is given.
+    #[arg(long, conflicts_with_all = ["output_path", "name_template"])]
+    output_file: Option<PathBuf>,
+
+    /// Alpha value (0-255) at or below which a pixel is treated as transparent when trimming.
+    #[arg(long, default_value_t = 0)]
+    alpha_threshold: u8,
+
+    /// What to trim from the edges: none, alpha or background.
+    #[arg(long, default_value = "alpha")]
+    trim_mode: TrimMode,
+
+    /// What alpha trimming does with images without an alpha channel, such as JPEGs: skip
+    /// the trim or trim the background color instead.
+    #[arg(long, default_value = "skip")]
+    opaque_policy: OpaquePolicy,
+
+    /// Background color (#RRGGBB) to trim in background mode. Detected from the corners if omitted.
+    #[arg(long)]
+    background: Option<Color>,
+
+    /// Maximum distance from the background color for a pixel to be trimmed.
+    #[arg(long, default_value_t = DEFAULT_COLOR_TOLERANCE)]
+    color_tolerance: f32,
+
+    /// Color space for background distance: rgb or lab.
+    #[arg(long, default_value = "lab")]
+    color_space: ColorSpace,
+
+    /// Border to keep around the trimmed content: pixels or a percentage, for all sides or
+    /// per side as vertical,horizontal or top,right,bottom,left (e.g. 8, 5%, 4,8,4,8).
+    #[arg(long, default_value = "0")]
+    margin: Margin,
+
+    /// When the margin reaches past the image: clamp to the image or extend the canvas
+    /// with transparency.
+    #[arg(long, default_value = "clamp")]
+    margin_mode: MarginMode,
+
+    /// Minimum aspect ratio (W:H or decimal). Defaults to 2:5.
+    #[arg(long)]
+    min_aspect: Option<AspectRatio>,
+
+    /// Maximum aspect ratio (W:H or decimal). Defaults to 5:2.
+    #[arg(long)]
+    max_aspect: Option<AspectRatio>,
+
+    /// Crop to exactly this aspect ratio (W:H or decimal).
+    #[arg(long, conflicts_with_all = ["min_aspect", "max_aspect"])]
+    aspect: Option<AspectRatio>,
+
+    /// Skip the aspect ratio step entirely.
+    #[arg(long, conflicts_with_all = ["min_aspect", "max_aspect", "aspect"])]
+    no_aspect: bool,
+
+    /// How to fit the aspect ratio: crop away content or pad the canvas.
+    #[arg(long, default_value = "crop")]
+    aspect_mode: AspectMode,
+
+    /// Where to keep the crop window: center, top, bottom, left, right or auto.
+    #[arg(long, default_value = "center")]
+    anchor: Anchor,
+
+    /// Fill for padded areas: transparent, edge, blur or #RRGGBB.
+    #[arg(long, default_value = "transparent")]
+    pad_fill: PadFill,
+
+    /// Process subdirectories too, mirroring their layout under the output directory.
+    #[arg(long, short)]
+    recursive: bool,
+
+    /// Only process files matching this glob (relative to the input directory). Repeatable.
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip files matching this glob (relative to the input directory). Repeatable.
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Crop files whose name matches this glob (e.g. 'walk_*.png') with one shared rectangle
+    /// per directory, so animation frames stay aligned. Repeatable.
+    #[arg(long)]
+    group: Vec<String>,
+
+    /// Share one crop rectangle among files grouped by: none, all, directory or sequence
+    /// (numbered frames like walk_001.png).
+    #[arg(long, default_value = "none")]
+    group_by: GroupBy,
+
+    /// What to do when an output file exists: never, always or if-newer.
+    #[arg(long, default_value = "always")]
+    overwrite: OverwritePolicy,
+
+    /// Replace each input file with its cropped version, written atomically.
+    #[arg(long, conflicts_with_all = ["output_path", "output_file", "name_template", "format"])]
+    in_place: bool,
+
+    /// Keep a .bak copy of every file that gets replaced.
+    #[arg(long)]
+    backup: bool,
+
+    /// Output file name template. Placeholders: {stem}, {ext}, {width}, {height}, {index},
+    /// {hash}, {parent}; numeric ones take a zero-padded width such as {index:04}.
+    #[arg(long, default_value = DEFAULT_NAME_TEMPLATE)]
+    name_template: NameTemplate,
+
+    /// Output format: png, jpeg, webp, avif, tiff, qoi, or same to keep the input format.
+    /// Defaults to png.
+    #[arg(long)]
+    format: Option<OutputFormat>,
+
+    /// JPEG quality (1-100).
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: u8,
+
+    /// PNG compression level: fast, default or best.
+    #[arg(long, default_value = "default")]
+    png_compression: PngCompression,
+
+    /// PNG filter: none, sub, up, avg, paeth or adaptive.
+    #[arg(long, default_value = "adaptive")]
+    png_filter: PngFilter,
+
+    /// Write lossy WebP instead of lossless.
+    #[arg(long)]
+    webp_lossy: bool,
+
+    /// Lossy WebP quality (0-100).
+    #[arg(long, default_value_t = 80.0, value_parser = webp_quality)]
+    webp_quality: f32,
+
+    /// Color (#RRGGBB) that transparency is blended onto for formats without alpha.
+    #[arg(long, default_value = "#ffffff")]
+    matte: Color,
+
+    /// Write a per-file report to this path, or to stdout with '-'.
+    #[arg(long, conflicts_with = "output_file")]
+    report: Option<PathBuf>,
+
+    /// Report layout: json for one array or ndjson for one object per line. Guessed from
+    /// the report file extension if omitted.
+    #[arg(long, requires = "report")]
+    report_format: Option<ReportFormat>,
+
+    /// Write a sidecar next to each output image with its placement in the original canvas
+    /// (TexturePacker spriteSourceSize/sourceSize): json or toml.
+    #[arg(long)]
+    sidecar: Option<SidecarFormat>,
+
+    /// Write one combined sprite manifest for all written images. TOML if the path ends in
+    /// .toml, JSON otherwise.
+    #[arg(long, conflicts_with = "output_file")]
+    manifest: Option<PathBuf>,
+
+    /// Compute crop boxes and output paths without encoding or writing any file.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Flag files whose crop removes more than this percentage of their area.
+    #[arg(long)]
+    max_area_loss: Option<f64>,
+
+    /// Attempt every file even after failures. This is the default; the exit code is
+    /// still non-zero if any file failed.
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Stop starting new files after the first failure.
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+
     /// Number of threads to use.
     #[arg(long, short, default_value_t = num_cpus::get())]
     num_threads: usize,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli_options = CliOptions::parse();
+fn main() -> ExitCode {
+    match run(CliOptions::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
+fn run(cli_options: CliOptions) -> Result<ExitCode, Box<dyn Error>> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(cli_options.num_threads)
         .build_global()?;
 
-    let output_path = cli_options.output_path.unwrap_or_else(|| {
-        let mut default_output = cli_options.input_path.clone();
-        default_output.push("output");
-        std::fs::create_dir_all(&default_output).expect("Failed to create output directory");
-        default_output
+    let mut pipeline = Pipeline::builder(
+        Cropper::builder()
+            .trim_mode(cli_options.trim_mode)
+            .opaque_policy(cli_options.opaque_policy)
+            .alpha_threshold(cli_options.alpha_threshold)
+            .background(cli_options.background)
+            .color_tolerance(cli_options.color_tolerance)
+            .color_space(cli_options.color_space)
+            .margin(cli_options.margin)
+            .margin_mode(cli_options.margin_mode)
+            .aspect(aspect_constraint(&cli_options)?)
+            .aspect_mode(cli_options.aspect_mode)
+            .anchor(cli_options.anchor)
+            .pad_fill(cli_options.pad_fill)
+            .build(),
+    )
+    .recursive(cli_options.recursive)
+    .output_format(output_format(&cli_options))
+    .name_template(cli_options.name_template.clone())
+    .overwrite(cli_options.overwrite)
+    // マニフェストには上書きしなかった出力も載せるので、その切り抜き範囲も求める
+    .crop_skipped(cli_options.manifest.is_some())
+    .in_place(cli_options.in_place)
+    .backup(cli_options.backup)
+    .fail_fast(cli_options.fail_fast)
+    .dry_run(cli_options.dry_run)
+    .sidecar(cli_options.sidecar)
+    .group_by(cli_options.group_by)
+    .encoder_options(EncoderOptions {
+        jpeg_quality: cli_options.jpeg_quality,
+        png_compression: cli_options.png_compression,
+        png_filter: cli_options.png_filter,
+        webp_lossless: !cli_options.webp_lossy,
+        webp_quality: cli_options.webp_quality,
+        matte: cli_options.matte,
     });
-
-    if cli_options.input_path.is_dir() {
-        process_directory(&cli_options.input_path, &output_path)?;
-    } else {
-        process_file(&cli_options.input_path, &output_path)?;
+    if let Some(max_area_loss) = cli_options.max_area_loss {
+        pipeline = pipeline.max_area_loss(max_area_loss);
     }
+    for pattern in &cli_options.include {
+        pipeline = pipeline.include(pattern);
+    }
+    for pattern in &cli_options.exclude {
+        pipeline = pipeline.exclude(pattern);
+    }
+    for pattern in &cli_options.group {
+        pipeline = pipeline.group(pattern);
+    }
+    let pipeline = pipeline.build()?;
 
-    Ok(())
-}
-
-fn process_directory(input_dir: &Path, output_dir: &Path) -> Result<(), Box<dyn Error>> {
-    let pattern = input_dir.join("*.png"); // Adjust pattern for different image formats if necessary
-    let output_dir = Arc::new(output_dir.to_path_buf());
-    glob(pattern.to_str().unwrap())?
-        .filter_map(Result::ok)
-        .par_bridge()
-        .for_each(|path| {
-            if let Err(e) = process_file(&path, &output_dir) {
-                eprintln!("Failed to process file {}: {}", path.display(), e);
-            }
-        });
-    Ok(())
-}
+    let stdin_input = cli_options.input_path.iter().any(|path| is_stdio(path));
+    let stdout_output = [&cli_options.output_path, &cli_options.output_file]
+        .into_iter()
+        .flatten()
+        .any(|path| is_stdio(path));
+    if stdin_input || stdout_output {
+        stream(&pipeline, &cli_options, stdin_input, stdout_output)?;
+        return Ok(ExitCode::SUCCESS);
+    }
 
-fn process_file(input_file: &Path, output_dir: &Path) -> Result<(), Box<dyn Error>> {
-    let img = image::open(input_file)?;
-    let cropped_img = crop_transparent_edges(&img);
-    let aspect_corrected_img = crop_to_aspect_ratio(cropped_img);
+    let mut inputs = cli_options.input_path.clone();
+    if let Some(list) = &cli_options.files_from {
+        inputs.extend(read_file_list(list)?);
+    }
 
-    let file_name = input_file.file_stem().unwrap().to_str().unwrap();
-    let output_file = output_dir.join(format!("{}_cropped.png", file_name));
-    aspect_corrected_img.save(output_file)?;
+    if let Some(output_file) = &cli_options.output_file {
+        let [input] = inputs.as_slice() else {
+            return Err("--output-file needs exactly one input file".into());
+        };
+        if !input.is_file() {
+            return Err(format!(
+                "--output-file needs a single input file, got {}",
+                input.display()
+            )
+            .into());
+        }
+        pipeline.process_file_to(input, output_file)?;
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    Ok(())
-}
+    let reports = pipeline.process_inputs(&inputs, cli_options.output_path.as_deref());
 
-fn crop_transparent_edges(img: &image::DynamicImage) -> image::DynamicImage {
-    let (width, height) = img.dimensions();
-    let mut top = 0;
-    let mut bottom = height;
-    let mut left = 0;
-    let mut right = width;
-
-    'outer: for y in 0..height {
-        for x in 0..width {
-            let pixel = img.get_pixel(x, y);
-            if pixel[3] != 0 {
-                top = y;
-                break 'outer;
-            }
+    if let Some(report_path) = &cli_options.report {
+        let format = cli_options
+            .report_format
+            .unwrap_or_else(|| ReportFormat::from_path(report_path));
+        if is_stdio(report_path) {
+            write_report(&reports, format, io::stdout().lock())?;
+        } else {
+            write_report(&reports, format, BufWriter::new(File::create(report_path)?))?;
         }
     }
 
-    'outer: for y in (0..height).rev() {
-        for x in 0..width {
-            let pixel = img.get_pixel(x, y);
-            if pixel[3] != 0 {
-                bottom = y + 1;
-                break 'outer;
-            }
+    if let Some(manifest_path) = &cli_options.manifest {
+        if !cli_options.dry_run {
+            write_manifest(&reports, manifest_path)?;
         }
     }
 
-    'outer: for x in 0..width {
-        for y in top..bottom {
-            let pixel = img.get_pixel(x, y);
-            if pixel[3] != 0 {
-                left = x;
-                break 'outer;
-            }
+    for report in &reports {
+        if let Err(e) = &report.result {
+            eprintln!("Failed to process file {}: {}", report.input.display(), e);
+        }
+        if let (true, Some(crop)) = (report.flagged, report.crop) {
+            eprintln!(
+                "Warning: cropping {} removes {:.1}% of its area",
+                report.input.display(),
+                crop.removed_area_percent()
+            );
         }
     }
+    let summary = Summary::from_reports(&reports);
+    eprintln!("{}", summary);
 
-    'outer: for x in (0..width).rev() {
-        for y in top..bottom {
-            let pixel = img.get_pixel(x, y);
-            if pixel[3] != 0 {
-                right = x + 1;
-                break 'outer;
-            }
-        }
+    // 1 件でも失敗があれば CI で検出できるよう非ゼロで終了する
+    Ok(if summary.failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Parses a lossy WebP quality, which libwebp only accepts between 0 and 100.
+fn webp_quality(s: &str) -> Result<f32, String> {
+    let quality: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=100.0).contains(&quality) {
+        Ok(quality)
+    } else {
+        Err(format!("{} is not in 0..=100", s))
     }
+}
 
-    img.crop_imm(left, top, right - left, bottom - top)
+/// Whether `path` is `-`, standing for stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Crops a single image between stdin/a file and stdout.
+fn stream(
+    pipeline: &Pipeline,
+    cli_options: &CliOptions,
+    stdin_input: bool,
+    stdout_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let [input] = cli_options.input_path.as_slice() else {
+        return Err("streaming through stdin or stdout needs exactly one input".into());
+    };
+    // ストリーミングではファイル単位の結果を残さないので、それに依存するオプションは拒否する
+    let unsupported = [
+        ("--files-from", cli_options.files_from.is_some()),
+        ("--dry-run", cli_options.dry_run),
+        ("--report", cli_options.report.is_some()),
+        ("--manifest", cli_options.manifest.is_some()),
+        ("--sidecar", cli_options.sidecar.is_some()),
+        ("--max-area-loss", cli_options.max_area_loss.is_some()),
+    ];
+    if let Some((flag, _)) = unsupported.iter().find(|(_, given)| *given) {
+        return Err(format!("{} cannot be combined with stdin or stdout streaming", flag).into());
+    }
+    if !stdout_output {
+        return Err("reading from stdin needs -o - to write to stdout".into());
+    }
+    if stdin_input {
+        pipeline.process_stream(io::stdin().lock(), io::stdout().lock())?;
+    } else {
+        pipeline.process_stream(File::open(input)?, io::stdout().lock())?;
+    }
+    Ok(())
+}
+
+/// Reads a newline- or NUL-separated list of paths from `source`, or from stdin for `-`.
+///
+/// The list is read as raw bytes, so on Unix paths need not be valid UTF-8.
+fn read_file_list(source: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    if source == Path::new("-") {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        File::open(source)?.read_to_end(&mut bytes)?;
+    }
+    // NUL が含まれていれば -print0 形式とみなす
+    let separator = if bytes.contains(&0) { 0 } else { b'\n' };
+    bytes
+        .split(|&b| b == separator)
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    std::str::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn output_format(cli_options: &CliOptions) -> OutputFormat {
+    if let Some(format) = cli_options.format {
+        return format;
+    }
+    // --output-file の拡張子から形式を推測し、分からなければ既定の PNG にする
+    cli_options
+        .output_file
+        .as_deref()
+        .and_then(|path| path.extension())
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| ext.parse().ok())
+        .unwrap_or_default()
 }
 
-fn crop_to_aspect_ratio(img: image::DynamicImage) -> image::DynamicImage {
-    let (width, height) = img.dimensions();
-    let aspect_ratio = width as f32 / height as f32;
-    let min_aspect = 2.0 / 5.0; // 最小アスペクト比 2:5
-    let max_aspect = 5.0 / 2.0; // 最大アスペクト比 5:2
-
-    if aspect_ratio < min_aspect {
-        // アスペクト比が小さい場合、高さを維持して幅を調整
-        let new_width = (height as f32 * min_aspect) as u32;
-        let new_left = (width - new_width) / 2;
-        img.crop_imm(new_left, 0, new_width, height)
-    } else if aspect_ratio > max_aspect {
-        // アスペクト比が大きい場合、幅を維持して高さを調整
-        let new_height = (width as f32 / max_aspect) as u32;
-        let new_top = (height - new_height) / 2;
-        img.crop_imm(0, new_top, width, new_height)
+fn aspect_constraint(cli_options: &CliOptions) -> image_cropper::Result<AspectConstraint> {
+    if cli_options.no_aspect {
+        Ok(AspectConstraint::None)
+    } else if let Some(aspect) = cli_options.aspect {
+        Ok(AspectConstraint::Exact(aspect.get()))
     } else {
-        img
+        AspectConstraint::range(
+            cli_options
+                .min_aspect
+                .map_or(DEFAULT_MIN_ASPECT, AspectRatio::get),
+            cli_options
+                .max_aspect
+                .map_or(DEFAULT_MAX_ASPECT, AspectRatio::get),
+        )
     }
 }
@@ -0,0 +1,170 @@
+use crate::cropper::CropBox;
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A margin width, either absolute or relative to the trimmed content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// A number of pixels.
+    Pixels(u32),
+    /// A percentage of the content's width (left and right) or height (top and bottom).
+    Percent(f32),
+}
+
+impl Length {
+    /// Returns the length in pixels for content that is `size` pixels across, saturating at
+    /// `u32::MAX`.
+    pub fn resolve(self, size: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => {
+                let pixels = (f64::from(size) * f64::from(percent) / 100.0).round();
+                pixels.min(f64::from(u32::MAX)) as u32
+            }
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::Pixels(0)
+    }
+}
+
+impl FromStr for Length {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::Parse(format!(
+                "invalid margin '{}', expected pixels such as 8 or a percentage such as 5%",
+                s
+            ))
+        };
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => {
+                let percent: f32 = percent.trim().parse().map_err(|_| invalid())?;
+                if !percent.is_finite() || percent < 0.0 {
+                    return Err(invalid());
+                }
+                Ok(Length::Percent(percent))
+            }
+            None => {
+                let pixels = s.strip_suffix("px").unwrap_or(s).trim();
+                pixels.parse().map(Length::Pixels).map_err(|_| invalid())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{}", pixels),
+            Length::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Border kept around the trimmed content.
+///
+/// Parsed like CSS margins: one value for all sides, `vertical,horizontal`,
+/// `top,horizontal,bottom` or `top,right,bottom,left`, e.g. `8`, `5%` or `4,8,4,8`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Margin {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Margin {
+    /// The same margin on every side.
+    pub fn uniform(length: Length) -> Self {
+        Self {
+            top: length,
+            right: length,
+            bottom: length,
+            left: length,
+        }
+    }
+
+    /// Grows `content` by the margin. With [`MarginMode::Clamp`] the result is limited to a
+    /// `width` x `height` image; with [`MarginMode::Extend`] it may reach beyond it.
+    pub fn expand(&self, content: CropBox, width: u32, height: u32, mode: MarginMode) -> CropBox {
+        let grow = |length: Length, size: u32| i64::from(length.resolve(size));
+        let expanded = CropBox {
+            left: content.left - grow(self.left, content.width()),
+            top: content.top - grow(self.top, content.height()),
+            right: content.right + grow(self.right, content.width()),
+            bottom: content.bottom + grow(self.bottom, content.height()),
+        };
+        match mode {
+            MarginMode::Clamp => expanded.clamp_to(width, height),
+            MarginMode::Extend => expanded,
+        }
+    }
+}
+
+impl FromStr for Margin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Length>, _>>()?;
+        match values[..] {
+            [all] => Ok(Margin::uniform(all)),
+            [vertical, horizontal] => Ok(Margin {
+                top: vertical,
+                right: horizontal,
+                bottom: vertical,
+                left: horizontal,
+            }),
+            [top, horizontal, bottom] => Ok(Margin {
+                top,
+                right: horizontal,
+                bottom,
+                left: horizontal,
+            }),
+            [top, right, bottom, left] => Ok(Margin {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            _ => Err(Error::Parse(format!(
+                "invalid margin '{}', expected 1 to 4 comma-separated values",
+                s
+            ))),
+        }
+    }
+}
+
+/// What happens when a margin reaches past the edge of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarginMode {
+    /// Keep the margin inside the original image.
+    #[default]
+    Clamp,
+    /// Add transparent canvas where the margin reaches past the image.
+    Extend,
+}
+
+impl FromStr for MarginMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clamp" => Ok(MarginMode::Clamp),
+            "extend" => Ok(MarginMode::Extend),
+            _ => Err(Error::Parse(format!(
+                "invalid margin mode '{}', expected clamp or extend",
+                s
+            ))),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Template that reproduces the historical `<stem>_cropped.<ext>` naming.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}_cropped.{ext}";
+
+/// Template for output file names, e.g. `{stem}_cropped.{ext}`.
+///
+/// Supported placeholders:
+///
+/// - `{stem}`: input file name without its extension
+/// - `{ext}`: extension of the output format
+/// - `{width}`, `{height}`: size of the cropped image
+/// - `{index}`: position of the file in the batch, starting at 0
+/// - `{hash}`: FNV-1a hash of the encoded output, as 16 hex digits
+/// - `{parent}`: name of the directory containing the input file
+///
+/// Numeric placeholders accept a zero-padded width such as `{index:04}`, and `{hash:8}`
+/// keeps the first 8 hex digits. `{{` and `}}` produce literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Field, Option<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Stem,
+    Ext,
+    Width,
+    Height,
+    Index,
+    Hash,
+    Parent,
+}
+
+/// Values substituted into a [`NameTemplate`].
+#[derive(Debug, Clone, Copy)]
+pub struct NameContext<'a> {
+    pub stem: &'a str,
+    pub ext: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub index: usize,
+    pub hash: u64,
+    pub parent: &'a str,
+}
+
+impl NameTemplate {
+    /// Whether rendered names depend on the cropped image itself, through `{width}`,
+    /// `{height}` or `{hash}`, and so are only known once the image has been cropped.
+    pub(crate) fn depends_on_image(&self) -> bool {
+        self.parts.iter().any(|part| {
+            matches!(
+                part,
+                Part::Placeholder(Field::Width | Field::Height | Field::Hash, _)
+            )
+        })
+    }
+
+    /// Substitutes the placeholders with the values in `ctx`.
+    pub fn render(&self, ctx: &NameContext) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match *part {
+                Part::Literal(ref text) => name.push_str(text),
+                Part::Placeholder(field, width) => {
+                    let width = width.unwrap_or(0);
+                    match field {
+                        Field::Stem => name.push_str(ctx.stem),
+                        Field::Ext => name.push_str(ctx.ext),
+                        Field::Parent => name.push_str(ctx.parent),
+                        Field::Width => name.push_str(&format!("{:0width$}", ctx.width)),
+                        Field::Height => name.push_str(&format!("{:0width$}", ctx.height)),
+                        Field::Index => name.push_str(&format!("{:0width$}", ctx.index)),
+                        Field::Hash => {
+                            let hash = format!("{:016x}", ctx.hash);
+                            let len = if width == 0 {
+                                hash.len()
+                            } else {
+                                width.min(hash.len())
+                            };
+                            name.push_str(&hash[..len]);
+                        }
+                    }
+                }
+            }
+        }
+        name
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        DEFAULT_NAME_TEMPLATE
+            .parse()
+            .expect("default name template is valid")
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| Error::Parse(format!("invalid name template '{}': {}", s, reason));
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => return Err(invalid("unclosed '{'")),
+                        }
+                    }
+                    let (name, width) = match spec.split_once(':') {
+                        Some((name, width)) => {
+                            let width = width.parse().map_err(|_| invalid("bad width"))?;
+                            (name, Some(width))
+                        }
+                        None => (spec.as_str(), None),
+                    };
+                    let field = match name {
+                        "stem" => Field::Stem,
+                        "ext" => Field::Ext,
+                        "width" => Field::Width,
+                        "height" => Field::Height,
+                        "index" => Field::Index,
+                        "hash" => Field::Hash,
+                        "parent" => Field::Parent,
+                        _ => return Err(invalid(&format!("unknown placeholder '{{{}}}'", name))),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(field, width));
+                }
+                '}' => return Err(invalid("unmatched '}'")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if parts.is_empty() {
+            return Err(invalid("template is empty"));
+        }
+        Ok(Self {
+            source: s.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across platforms and Rust versions.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -0,0 +1,319 @@
+use crate::color::Color;
+use crate::error::{Error, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{self, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// Format in which cropped images are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Reuse the format of the input file, falling back to PNG if it cannot be written.
+    SameAsInput,
+    /// Always write this format.
+    Format(ImageFormat),
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Format(ImageFormat::Png)
+    }
+}
+
+impl OutputFormat {
+    /// Returns the concrete format for an image that was decoded from `input`.
+    pub fn resolve(self, input: Option<ImageFormat>) -> ImageFormat {
+        match self {
+            OutputFormat::Format(format) => format,
+            OutputFormat::SameAsInput => input
+                .filter(ImageFormat::writing_enabled)
+                .unwrap_or(ImageFormat::Png),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("same") {
+            return Ok(OutputFormat::SameAsInput);
+        }
+        match ImageFormat::from_extension(s) {
+            Some(format) if format.writing_enabled() => Ok(OutputFormat::Format(format)),
+            _ => Err(Error::Parse(format!(
+                "invalid output format '{}', expected same, png, jpeg, webp, avif, tiff, qoi or another writable format",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::SameAsInput => f.write_str("same"),
+            OutputFormat::Format(format) => f.write_str(extension(*format)),
+        }
+    }
+}
+
+/// Returns the file extension used for `format`.
+pub fn extension(format: ImageFormat) -> &'static str {
+    format.extensions_str().first().copied().unwrap_or("img")
+}
+
+/// PNG compression level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl FromStr for PngCompression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fast" => Ok(PngCompression::Fast),
+            "default" => Ok(PngCompression::Default),
+            "best" => Ok(PngCompression::Best),
+            _ => Err(Error::Parse(format!(
+                "invalid PNG compression '{}', expected fast, default or best",
+                s
+            ))),
+        }
+    }
+}
+
+/// PNG scanline filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+impl FromStr for PngFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(PngFilter::None),
+            "sub" => Ok(PngFilter::Sub),
+            "up" => Ok(PngFilter::Up),
+            "avg" => Ok(PngFilter::Avg),
+            "paeth" => Ok(PngFilter::Paeth),
+            "adaptive" => Ok(PngFilter::Adaptive),
+            _ => Err(Error::Parse(format!(
+                "invalid PNG filter '{}', expected none, sub, up, avg, paeth or adaptive",
+                s
+            ))),
+        }
+    }
+}
+
+/// Per-encoder settings used when writing cropped images.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncoderOptions {
+    /// JPEG quality, 1-100.
+    pub jpeg_quality: u8,
+    /// PNG compression level.
+    pub png_compression: PngCompression,
+    /// PNG scanline filter.
+    pub png_filter: PngFilter,
+    /// Write lossless WebP. Lossy WebP needs the `webp-lossy` feature.
+    pub webp_lossless: bool,
+    /// Lossy WebP quality, 0-100.
+    pub webp_quality: f32,
+    /// Color that transparent pixels are blended onto for formats without alpha, such as JPEG.
+    pub matte: Color,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 90,
+            png_compression: PngCompression::Default,
+            png_filter: PngFilter::Adaptive,
+            webp_lossless: true,
+            webp_quality: 80.0,
+            matte: Color::WHITE,
+        }
+    }
+}
+
+/// Encodes `img` as `format` and returns the encoded bytes.
+///
+/// The image is converted to a color type the encoder accepts first; alpha is blended onto
+/// [`EncoderOptions::matte`] for formats that cannot store it.
+pub fn encode_image(
+    img: &DynamicImage,
+    format: ImageFormat,
+    options: &EncoderOptions,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut bytes, options.jpeg_quality);
+            flatten(img, options.matte).write_with_encoder(encoder)?;
+        }
+        ImageFormat::Png => {
+            let encoder = PngEncoder::new_with_quality(
+                &mut bytes,
+                options.png_compression.into(),
+                options.png_filter.into(),
+            );
+            to_integer(img).write_with_encoder(encoder)?;
+        }
+        ImageFormat::WebP if options.webp_lossless => {
+            to_8bit(img).write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?;
+        }
+        ImageFormat::WebP => bytes = encode_lossy_webp(img, options.webp_quality)?,
+        ImageFormat::Tiff => {
+            let img = match to_integer(img) {
+                DynamicImage::ImageLumaA8(buf) => DynamicImage::ImageLumaA8(buf).to_rgba8().into(),
+                DynamicImage::ImageLumaA16(buf) => {
+                    DynamicImage::ImageLumaA16(buf).to_rgba16().into()
+                }
+                other => other,
+            };
+            img.write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        // 以下の形式はエンコーダが受け付ける色形式が限られている
+        ImageFormat::Qoi | ImageFormat::Gif => {
+            to_rgb8_or_rgba8(img).write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        ImageFormat::Ico => {
+            // ICO 内の PNG は RGBA でないと読み込めない
+            DynamicImage::ImageRgba8(img.to_rgba8())
+                .write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        ImageFormat::Farbfeld => {
+            DynamicImage::ImageRgba16(img.to_rgba16())
+                .write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        ImageFormat::OpenExr if img.color().has_alpha() => {
+            DynamicImage::ImageRgba32F(img.to_rgba32f())
+                .write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        ImageFormat::OpenExr => {
+            DynamicImage::ImageRgb32F(img.to_rgb32f())
+                .write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        ImageFormat::Hdr => {
+            let img = if img.color().has_alpha() {
+                flatten(img, options.matte)
+            } else {
+                img.clone()
+            };
+            DynamicImage::ImageRgb32F(img.to_rgb32f())
+                .write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        _ => to_8bit(img).write_to(&mut Cursor::new(&mut bytes), format)?,
+    }
+    Ok(bytes)
+}
+
+#[cfg(feature = "webp-lossy")]
+fn encode_lossy_webp(img: &DynamicImage, quality: f32) -> Result<Vec<u8>> {
+    let img = to_8bit(img);
+    let (width, height) = (img.width(), img.height());
+    // Encoder::encode は失敗すると panic するので Result を返す encode_simple を使う
+    let encoded = match &img {
+        DynamicImage::ImageRgba8(buf) => {
+            webp::Encoder::from_rgba(buf, width, height).encode_simple(false, quality)
+        }
+        _ => webp::Encoder::from_rgb(&img.to_rgb8(), width, height).encode_simple(false, quality),
+    };
+    encoded
+        .map(|memory| memory.to_vec())
+        .map_err(|e| Error::Encode(format!("lossy WebP: {:?}", e)))
+}
+
+#[cfg(not(feature = "webp-lossy"))]
+fn encode_lossy_webp(_img: &DynamicImage, _quality: f32) -> Result<Vec<u8>> {
+    Err(Error::Unsupported(
+        "lossy WebP requires the webp-lossy feature".to_string(),
+    ))
+}
+
+/// Blends `img` onto `matte` and drops the alpha channel.
+pub fn flatten(img: &DynamicImage, matte: Color) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return DynamicImage::ImageRgb8(img.to_rgb8());
+    }
+    let rgba = img.to_rgba8();
+    let matte = [matte.r, matte.g, matte.b];
+    let flattened = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = u32::from(pixel[3]);
+        let blend = |c: usize| {
+            ((u32::from(pixel[c]) * alpha + u32::from(matte[c]) * (255 - alpha) + 127) / 255) as u8
+        };
+        image::Rgb([blend(0), blend(1), blend(2)])
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
+
+/// Converts float images to 16-bit, leaving integer images untouched.
+fn to_integer(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgb16(img.to_rgb16()),
+        DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgba16(img.to_rgba16()),
+        _ => img.clone(),
+    }
+}
+
+/// Converts to 8-bit RGBA if the image has alpha, and to 8-bit RGB otherwise.
+fn to_rgb8_or_rgba8(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => img.clone(),
+        _ if img.color().has_alpha() => DynamicImage::ImageRgba8(img.to_rgba8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    }
+}
+
+/// Converts to 8-bit RGB or RGBA, keeping grayscale 8-bit images as they are.
+fn to_8bit(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_) => img.clone(),
+        _ if img.color().has_alpha() => DynamicImage::ImageRgba8(img.to_rgba8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    }
+}
+
+impl From<PngCompression> for png::CompressionType {
+    fn from(c: PngCompression) -> Self {
+        match c {
+            PngCompression::Fast => png::CompressionType::Fast,
+            PngCompression::Default => png::CompressionType::Default,
+            PngCompression::Best => png::CompressionType::Best,
+        }
+    }
+}
+
+impl From<PngFilter> for png::FilterType {
+    fn from(f: PngFilter) -> Self {
+        match f {
+            PngFilter::None => png::FilterType::NoFilter,
+            PngFilter::Sub => png::FilterType::Sub,
+            PngFilter::Up => png::FilterType::Up,
+            PngFilter::Avg => png::FilterType::Avg,
+            PngFilter::Paeth => png::FilterType::Paeth,
+            PngFilter::Adaptive => png::FilterType::Adaptive,
+        }
+    }
+}
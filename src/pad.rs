@@ -0,0 +1,231 @@
+use crate::color::Color;
+use crate::error::Error;
+use image::imageops::{self, FilterType};
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgba};
+use std::str::FromStr;
+
+/// How the area added by padding is filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PadFill {
+    /// Fully transparent pixels.
+    #[default]
+    Transparent,
+    /// A solid opaque color.
+    Color(Color),
+    /// Repeat the outermost row or column of the image.
+    Edge,
+    /// A blurred, scaled-up copy of the image.
+    Blur,
+}
+
+impl FromStr for PadFill {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "transparent" => Ok(PadFill::Transparent),
+            "edge" => Ok(PadFill::Edge),
+            "blur" => Ok(PadFill::Blur),
+            _ => s.parse().map(PadFill::Color).map_err(|_| {
+                Error::Parse(format!(
+                    "invalid pad fill '{}', expected transparent, edge, blur or #RRGGBB",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+/// Extends the canvas of `img` so that its aspect ratio lies within `min_aspect..=max_aspect`.
+///
+/// The original pixels are kept and centred; the new area is filled according to `fill`.
+pub fn pad_to_aspect_ratio(
+    img: image::DynamicImage,
+    min_aspect: f32,
+    max_aspect: f32,
+    fill: PadFill,
+) -> image::DynamicImage {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return img;
+    }
+    let aspect_ratio = width as f32 / height as f32;
+
+    let (canvas_width, canvas_height) = if aspect_ratio < min_aspect {
+        // 縦長すぎる場合は左右に余白を足す
+        let new_width = (f64::from(height) * f64::from(min_aspect)).round() as u32;
+        (new_width.max(width), height)
+    } else if aspect_ratio > max_aspect {
+        // 横長すぎる場合は上下に余白を足す
+        let new_height = (f64::from(width) / f64::from(max_aspect)).round() as u32;
+        (width, new_height.max(height))
+    } else {
+        return img;
+    };
+    if (canvas_width, canvas_height) == (width, height) {
+        return img;
+    }
+
+    let color_type = img.color();
+    let padded = match img {
+        DynamicImage::ImageRgba32F(_) | DynamicImage::ImageRgb32F(_) => {
+            let fill = fill.pixel(|c| f32::from(c) / 255.0, 0.0, 1.0);
+            DynamicImage::ImageRgba32F(pad_buffer(
+                &img.to_rgba32f(),
+                canvas_width,
+                canvas_height,
+                fill,
+            ))
+        }
+        _ if color_type.bytes_per_pixel() / color_type.channel_count() == 2 => {
+            let fill = fill.pixel(|c| u16::from(c) * 257, 0, u16::MAX);
+            DynamicImage::ImageRgba16(pad_buffer(
+                &img.to_rgba16(),
+                canvas_width,
+                canvas_height,
+                fill,
+            ))
+        }
+        _ => {
+            let fill = fill.pixel(|c| c, 0, u8::MAX);
+            DynamicImage::ImageRgba8(pad_buffer(
+                &img.to_rgba8(),
+                canvas_width,
+                canvas_height,
+                fill,
+            ))
+        }
+    };
+
+    if color_type.has_alpha() || fill == PadFill::Transparent {
+        padded
+    } else {
+        convert_to(padded, color_type)
+    }
+}
+
+/// Fill resolved for a concrete pixel type.
+enum Fill<P> {
+    Solid(P),
+    Edge,
+    Blur,
+}
+
+impl PadFill {
+    fn pixel<T: Copy>(self, scale: impl Fn(u8) -> T, zero: T, max: T) -> Fill<Rgba<T>> {
+        match self {
+            PadFill::Transparent => Fill::Solid(Rgba([zero, zero, zero, zero])),
+            PadFill::Color(c) => Fill::Solid(Rgba([scale(c.r), scale(c.g), scale(c.b), max])),
+            PadFill::Edge => Fill::Edge,
+            PadFill::Blur => Fill::Blur,
+        }
+    }
+}
+
+fn pad_buffer<P>(
+    src: &ImageBuffer<P, Vec<P::Subpixel>>,
+    canvas_width: u32,
+    canvas_height: u32,
+    fill: Fill<P>,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+{
+    let (width, height) = src.dimensions();
+    let offset_x = (canvas_width - width) / 2;
+    let offset_y = (canvas_height - height) / 2;
+
+    let mut canvas = match fill {
+        Fill::Solid(pixel) => ImageBuffer::from_pixel(canvas_width, canvas_height, pixel),
+        Fill::Edge => ImageBuffer::from_fn(canvas_width, canvas_height, |x, y| {
+            let src_x = x.saturating_sub(offset_x).min(width - 1);
+            let src_y = y.saturating_sub(offset_y).min(height - 1);
+            *src.get_pixel(src_x, src_y)
+        }),
+        Fill::Blur => {
+            // キャンバス全体を覆うように拡大してから中央を切り出し、ぼかす
+            let scale = f64::max(
+                f64::from(canvas_width) / f64::from(width),
+                f64::from(canvas_height) / f64::from(height),
+            );
+            let scaled_width = ((f64::from(width) * scale).ceil() as u32).max(canvas_width);
+            let scaled_height = ((f64::from(height) * scale).ceil() as u32).max(canvas_height);
+            let scaled = imageops::resize(src, scaled_width, scaled_height, FilterType::Triangle);
+            let covered = imageops::crop_imm(
+                &scaled,
+                (scaled_width - canvas_width) / 2,
+                (scaled_height - canvas_height) / 2,
+                canvas_width,
+                canvas_height,
+            )
+            .to_image();
+            let sigma = canvas_width.max(canvas_height) as f32 / 40.0;
+            imageops::blur(&covered, sigma.max(1.0))
+        }
+    };
+    imageops::replace(&mut canvas, src, i64::from(offset_x), i64::from(offset_y));
+    canvas
+}
+
+/// Converts a padded RGBA image back to the alpha-less `color_type` it came from.
+fn convert_to(img: DynamicImage, color_type: ColorType) -> DynamicImage {
+    match color_type {
+        ColorType::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(img.to_luma16()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        _ => img,
+    }
+}
+
+/// Cuts the `width` x `height` area at `(left, top)` out of `img`. The area may reach past
+/// the image; pixels outside it are transparent.
+pub(crate) fn extract_area(
+    img: &DynamicImage,
+    left: i64,
+    top: i64,
+    width: u32,
+    height: u32,
+) -> DynamicImage {
+    let (img_width, img_height) = img.dimensions();
+    let right = left + i64::from(width);
+    let bottom = top + i64::from(height);
+    let x0 = left.clamp(0, i64::from(img_width));
+    let y0 = top.clamp(0, i64::from(img_height));
+    let x1 = right.clamp(0, i64::from(img_width));
+    let y1 = bottom.clamp(0, i64::from(img_height));
+    // 画像内に収まっていれば普通に切り出す
+    if (x0, y0, x1, y1) == (left, top, right, bottom) {
+        return img.crop_imm(left as u32, top as u32, width, height);
+    }
+
+    let inner = img.crop_imm(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32);
+    let (x, y) = (x0 - left, y0 - top);
+    let color_type = img.color();
+    match img {
+        DynamicImage::ImageRgba32F(_) | DynamicImage::ImageRgb32F(_) => {
+            DynamicImage::ImageRgba32F(place(&inner.to_rgba32f(), width, height, x, y))
+        }
+        _ if color_type.bytes_per_pixel() / color_type.channel_count() == 2 => {
+            DynamicImage::ImageRgba16(place(&inner.to_rgba16(), width, height, x, y))
+        }
+        _ => DynamicImage::ImageRgba8(place(&inner.to_rgba8(), width, height, x, y)),
+    }
+}
+
+/// Places `src` at `(x, y)` on a transparent `width` x `height` canvas.
+fn place<P>(
+    src: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+    x: i64,
+    y: i64,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+{
+    let mut canvas = ImageBuffer::new(width, height);
+    imageops::replace(&mut canvas, src, x, y);
+    canvas
+}
@@ -0,0 +1,778 @@
+use crate::cropper::{CropBox, CropInfo, Cropper};
+use crate::discover::{discover_images, expand_glob, glob_base, is_glob, FileFilter};
+use crate::error::{Error, Result};
+use crate::group::{GroupBy, GroupKey, Grouping};
+use crate::naming::{fnv1a, NameContext, NameTemplate};
+use crate::output::{encode_image, extension, EncoderOptions, OutputFormat};
+use crate::sidecar::{write_sidecar, SidecarFormat};
+use crate::write::{write_atomic, write_atomic_new, OverwritePolicy};
+use image::{DynamicImage, GenericImageView};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::{BufRead, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// What happened to a single input file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The cropped image was written to this path.
+    Written(PathBuf),
+    /// An output already existed at this path and the overwrite policy kept it.
+    Skipped(PathBuf),
+    /// Dry run: the cropped image would have been written to this path.
+    WouldWrite(PathBuf),
+    /// Fail-fast mode stopped the batch before this file was started.
+    NotAttempted,
+}
+
+impl Outcome {
+    /// Returns the output path, unless the file was never attempted.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Outcome::Written(path) | Outcome::Skipped(path) | Outcome::WouldWrite(path) => {
+                Some(path)
+            }
+            Outcome::NotAttempted => None,
+        }
+    }
+}
+
+/// Result of processing one input file in a batch.
+#[derive(Debug)]
+pub struct FileReport {
+    /// The input file.
+    pub input: PathBuf,
+    /// Whether the file was written, skipped or failed.
+    pub result: Result<Outcome>,
+    /// What the cropper did, if the image could be decoded and cropped.
+    pub crop: Option<CropInfo>,
+    /// Time spent on this file, from decoding to writing.
+    pub duration: Duration,
+    /// Whether the crop removed more of the image than [`PipelineBuilder::max_area_loss`]
+    /// allows.
+    pub flagged: bool,
+}
+
+impl FileReport {
+    /// A report for a file that fail-fast mode never started.
+    fn not_attempted(input: PathBuf) -> Self {
+        Self {
+            input,
+            result: Ok(Outcome::NotAttempted),
+            crop: None,
+            duration: Duration::ZERO,
+            flagged: false,
+        }
+    }
+}
+
+/// Reads images from disk, crops them with a [`Cropper`] and writes the results.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    cropper: Cropper,
+    filter: FileFilter,
+    recursive: bool,
+    output_format: OutputFormat,
+    encoder_options: EncoderOptions,
+    name_template: NameTemplate,
+    overwrite: OverwritePolicy,
+    crop_skipped: bool,
+    in_place: bool,
+    backup: bool,
+    fail_fast: bool,
+    dry_run: bool,
+    max_area_loss: Option<f64>,
+    sidecar: Option<SidecarFormat>,
+    grouping: Grouping,
+}
+
+impl Pipeline {
+    /// Creates a pipeline that crops with `cropper` and uses the default file settings.
+    pub fn new(cropper: Cropper) -> Self {
+        Self {
+            cropper,
+            filter: FileFilter::default(),
+            recursive: false,
+            output_format: OutputFormat::default(),
+            encoder_options: EncoderOptions::default(),
+            name_template: NameTemplate::default(),
+            overwrite: OverwritePolicy::default(),
+            crop_skipped: false,
+            in_place: false,
+            backup: false,
+            fail_fast: false,
+            dry_run: false,
+            max_area_loss: None,
+            sidecar: None,
+            grouping: Grouping::default(),
+        }
+    }
+
+    /// Returns a builder for a pipeline that crops with `cropper`.
+    pub fn builder(cropper: Cropper) -> PipelineBuilder {
+        PipelineBuilder {
+            pipeline: Self::new(cropper),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            group_patterns: Vec::new(),
+            group_by: GroupBy::None,
+        }
+    }
+
+    /// Returns the cropper used by this pipeline.
+    pub fn cropper(&self) -> &Cropper {
+        &self.cropper
+    }
+
+    /// Crops every image in `input_dir` in parallel and writes them to `output_dir`.
+    ///
+    /// Files are picked up if their extension or content identifies a format the `image`
+    /// crate can decode, and if they pass the include/exclude filters.
+    ///
+    /// In recursive mode subdirectories are processed as well and their layout is recreated
+    /// under `output_dir`; `output_dir` itself is skipped when it lies inside `input_dir`.
+    ///
+    /// A failure on one file does not stop the others; a [`FileReport`] is returned for
+    /// each file.
+    pub fn process_directory(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<Vec<FileReport>> {
+        let skip_dir = (!self.in_place).then_some(output_dir);
+        let jobs = discover_images(input_dir, &self.filter, self.recursive, skip_dir)?
+            .into_iter()
+            .map(|relative| Job::new(input_dir, &relative, output_dir))
+            .collect();
+        Ok(self.run_jobs(jobs))
+    }
+
+    /// Crops every image named by `inputs` in parallel.
+    ///
+    /// Each input may be a file, a directory (processed like [`Pipeline::process_directory`])
+    /// or a glob pattern such as `assets/**/*.png`. Explicitly named files are always
+    /// processed; images found through directories and globs must pass the include/exclude
+    /// filters. Output goes to `output_dir`, or to [`default_output_dir`] of each input
+    /// when it is `None`, with directory and glob layouts mirrored below it.
+    ///
+    /// An input that does not exist or cannot be listed is reported as a failed entry
+    /// instead of stopping the batch.
+    pub fn process_inputs(&self, inputs: &[PathBuf], output_dir: Option<&Path>) -> Vec<FileReport> {
+        let mut jobs = Vec::new();
+        let mut failures = Vec::new();
+        for input in inputs {
+            if let Err(e) = self.collect_jobs(input, output_dir, &mut jobs) {
+                failures.push(FileReport {
+                    input: input.clone(),
+                    result: Err(e),
+                    crop: None,
+                    duration: Duration::ZERO,
+                    flagged: false,
+                });
+            }
+        }
+        if self.fail_fast && !failures.is_empty() {
+            failures.extend(
+                jobs.into_iter()
+                    .map(|job| FileReport::not_attempted(job.input)),
+            );
+            return failures;
+        }
+        failures.extend(self.run_jobs(jobs));
+        failures
+    }
+
+    /// Appends the files that `input` expands to onto `jobs`.
+    fn collect_jobs(
+        &self,
+        input: &Path,
+        output_dir: Option<&Path>,
+        jobs: &mut Vec<Job>,
+    ) -> Result<()> {
+        let output_dir = match output_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => default_output_dir(input)?,
+        };
+        let skip_dir = (!self.in_place).then_some(output_dir.as_path());
+        if input.is_dir() {
+            for relative in discover_images(input, &self.filter, self.recursive, skip_dir)? {
+                jobs.push(Job::new(input, &relative, &output_dir));
+            }
+        } else if input.is_file() {
+            jobs.push(Job {
+                input: input.to_path_buf(),
+                output_dir,
+            });
+        } else if is_glob(input) {
+            let base = glob_base(input);
+            for relative in expand_glob(input, &self.filter, skip_dir)? {
+                jobs.push(Job::new(&base, &relative, &output_dir));
+            }
+        } else {
+            return Err(Error::NotFound(input.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Processes `jobs` on the rayon pool, numbering them in order for `{index}`.
+    ///
+    /// In fail-fast mode jobs that have not started when a file fails are reported as
+    /// [`Outcome::NotAttempted`]. Jobs that would write the same output as another job fail
+    /// without being processed.
+    fn run_jobs(&self, jobs: Vec<Job>) -> Vec<FileReport> {
+        let plans = self.group_plans(&jobs);
+        let conflicts = self.output_conflicts(&jobs);
+        let failed = AtomicBool::new(false);
+        jobs.into_par_iter()
+            .zip(plans.into_par_iter().zip(conflicts))
+            .enumerate()
+            .map(|(index, (job, (plan, conflict)))| {
+                if self.fail_fast && failed.load(Ordering::Relaxed) {
+                    return FileReport::not_attempted(job.input);
+                }
+                let start = Instant::now();
+                let outcome = match conflict {
+                    Some((output, other)) => Err(Error::DuplicateOutput(output, other)),
+                    None => self.process_file_at(&job.input, &job.output_dir, index, plan.as_ref()),
+                };
+                let (result, crop) = match outcome {
+                    Ok((outcome, crop)) => (Ok(outcome), crop),
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        (Err(e), None)
+                    }
+                };
+                let flagged = match (crop, self.max_area_loss) {
+                    (Some(crop), Some(max)) => crop.removed_area_percent() > max,
+                    _ => false,
+                };
+                FileReport {
+                    input: job.input,
+                    result,
+                    crop,
+                    duration: start.elapsed(),
+                    flagged,
+                }
+            })
+            .collect()
+    }
+
+    /// Finds jobs whose output path is shared with another job, returning that path and the
+    /// other job's input for each of them.
+    ///
+    /// Only output names that are known before decoding can be compared, so templates using
+    /// `{width}`, `{height}` or `{hash}` are not checked. With [`OutputFormat::SameAsInput`]
+    /// the input format is taken from the file extension.
+    fn output_conflicts(&self, jobs: &[Job]) -> Vec<Option<(PathBuf, PathBuf)>> {
+        let mut conflicts = vec![None; jobs.len()];
+        if self.in_place || self.name_template.depends_on_image() {
+            return conflicts;
+        }
+        let mut claimed: HashMap<PathBuf, usize> = HashMap::new();
+        for (i, job) in jobs.iter().enumerate() {
+            let format = self
+                .output_format
+                .resolve(image::ImageFormat::from_path(&job.input).ok());
+            let Ok(output) = self.output_path(&job.input, &job.output_dir, format, i, (0, 0), 0)
+            else {
+                continue;
+            };
+            match claimed.get(&output) {
+                Some(&first) => {
+                    // 同じ出力先になるファイルはどれも書かずに失敗させる
+                    conflicts[first].get_or_insert((output.clone(), job.input.clone()));
+                    conflicts[i] = Some((output, jobs[first].input.clone()));
+                }
+                None => {
+                    claimed.insert(output, i);
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Returns the shared crop of each job's group, or `None` for jobs cropped on their own.
+    fn group_plans(&self, jobs: &[Job]) -> Vec<Option<CropInfo>> {
+        let mut plans = vec![None; jobs.len()];
+        if !self.grouping.is_enabled() {
+            return plans;
+        }
+        let mut groups: HashMap<GroupKey, Vec<usize>> = HashMap::new();
+        for (i, job) in jobs.iter().enumerate() {
+            if let Some(key) = self.grouping.key(&job.input) {
+                groups.entry(key).or_default().push(i);
+            }
+        }
+        let computed: Vec<_> = groups
+            .into_par_iter()
+            .map(|(_, members)| {
+                let inputs: Vec<&Path> = members.iter().map(|&i| jobs[i].input.as_path()).collect();
+                (members, self.group_plan(&inputs))
+            })
+            .collect();
+        for (members, plan) in computed {
+            for i in members {
+                plans[i] = plan;
+            }
+        }
+        plans
+    }
+
+    /// Computes one crop for all of `inputs`: the union of their trim boxes, followed by the
+    /// aspect correction chosen for the first frame.
+    ///
+    /// Frames that cannot be decoded or whose size differs from the first frame are left
+    /// out of the union; they fail on their own when processed.
+    fn group_plan(&self, inputs: &[&Path]) -> Option<CropInfo> {
+        // 全フレームを保持するとメモリを食うので、ここでは枠だけ求めて後で再デコードする
+        let boxes: Vec<_> = inputs
+            .par_iter()
+            .enumerate()
+            .filter_map(|(i, path)| {
+                let img = decode(path).ok()?;
+                Some((i, img.dimensions(), self.cropper.trim_box(&img)))
+            })
+            .collect();
+        let &(first, size, _) = boxes.first()?;
+        let union = boxes
+            .iter()
+            .filter(|&&(_, dims, _)| dims == size)
+            .map(|&(_, _, trim)| trim)
+            .reduce(CropBox::union)?;
+        let img = decode(inputs[first]).ok()?;
+        let (_, plan) = self.cropper.crop_with_trim(&img, union).ok()?;
+        Some(plan)
+    }
+
+    /// Crops a single image and writes it into `output_dir`.
+    ///
+    /// In in-place mode the input file is replaced instead and `output_dir` is ignored.
+    pub fn process_file(&self, input_file: &Path, output_dir: &Path) -> Result<Outcome> {
+        self.process_file_at(input_file, output_dir, 0, None)
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Crops a single image and writes it to exactly `output_file`.
+    ///
+    /// The name template is not used; the overwrite policy still applies.
+    pub fn process_file_to(&self, input_file: &Path, output_file: &Path) -> Result<Outcome> {
+        if self.kept_before_decoding(input_file, output_file)? {
+            return Ok(Outcome::Skipped(output_file.to_path_buf()));
+        }
+        let encoded = self.crop_and_encode(input_file, None)?;
+        self.write_output(input_file, output_file, &encoded)
+    }
+
+    /// Crops an image read from `input` and writes the encoded result to `output`.
+    ///
+    /// The input format is sniffed from the data, and [`OutputFormat::SameAsInput`] resolves
+    /// to it. Name templates, the overwrite policy and in-place mode do not apply.
+    pub fn process_stream(&self, mut input: impl Read, mut output: impl Write) -> Result<()> {
+        // デコーダはシークを必要とするので一度すべて読み込む
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let reader = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+        let format = self.output_format.resolve(reader.format());
+        let encoded = self.crop_and_encode_from(reader, format, None)?;
+        output.write_all(&encoded.bytes)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Like [`Pipeline::process_file`], with `index` as the file's position in the batch and
+    /// `plan` as the crop shared by the file's group.
+    ///
+    /// The crop is `None` when the file was skipped before it was decoded.
+    fn process_file_at(
+        &self,
+        input_file: &Path,
+        output_dir: &Path,
+        index: usize,
+        plan: Option<&CropInfo>,
+    ) -> Result<(Outcome, Option<CropInfo>)> {
+        if !self.in_place && !self.name_template.depends_on_image() {
+            // 出力先が画像の中身に依らなければ、デコード前に上書きの可否を判定できる
+            let reader = image::ImageReader::open(input_file)?.with_guessed_format()?;
+            let format = self.output_format.resolve(reader.format());
+            let output_file = self.output_path(input_file, output_dir, format, index, (0, 0), 0)?;
+            if self.kept_before_decoding(input_file, &output_file)? {
+                return Ok((Outcome::Skipped(output_file), None));
+            }
+        }
+
+        let encoded = self.crop_and_encode(input_file, plan)?;
+
+        if self.in_place {
+            if self.dry_run {
+                return Ok((
+                    Outcome::WouldWrite(input_file.to_path_buf()),
+                    Some(encoded.crop),
+                ));
+            }
+            write_atomic(input_file, &encoded.bytes, self.backup)?;
+            self.write_sidecar(input_file, &encoded)?;
+            return Ok((
+                Outcome::Written(input_file.to_path_buf()),
+                Some(encoded.crop),
+            ));
+        }
+
+        // ドライランではエンコードしないのでハッシュは 0 になる
+        let hash = if self.dry_run {
+            0
+        } else {
+            fnv1a(&encoded.bytes)
+        };
+        let output_file = self.output_path(
+            input_file,
+            output_dir,
+            encoded.format,
+            index,
+            encoded.crop.final_size,
+            hash,
+        )?;
+        let outcome = self.write_output(input_file, &output_file, &encoded)?;
+        Ok((outcome, Some(encoded.crop)))
+    }
+
+    /// Returns the path in `output_dir` that the name template gives the output of
+    /// `input_file`.
+    ///
+    /// Parts of the input path that are not valid UTF-8 are rendered with U+FFFD in place of
+    /// the invalid bytes.
+    fn output_path(
+        &self,
+        input_file: &Path,
+        output_dir: &Path,
+        format: image::ImageFormat,
+        index: usize,
+        (width, height): (u32, u32),
+        hash: u64,
+    ) -> Result<PathBuf> {
+        let stem = input_file
+            .file_stem()
+            .ok_or_else(|| Error::InvalidPath(input_file.to_path_buf()))?
+            .to_string_lossy();
+        let parent = input_file
+            .parent()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let file_name = self.name_template.render(&NameContext {
+            stem: &stem,
+            ext: extension(format),
+            width,
+            height,
+            index,
+            hash,
+            parent: &parent,
+        });
+        Ok(output_dir.join(file_name))
+    }
+
+    /// Whether the overwrite policy keeps the existing `output_file`, so that `input_file`
+    /// can be skipped without decoding it.
+    ///
+    /// Always false with [`PipelineBuilder::crop_skipped`], and when the output would be the
+    /// input itself, which is rejected once the file has been cropped.
+    fn kept_before_decoding(&self, input_file: &Path, output_file: &Path) -> Result<bool> {
+        if self.crop_skipped || is_same_file(input_file, output_file) {
+            return Ok(false);
+        }
+        Ok(!self.overwrite.allows(input_file, output_file)?)
+    }
+
+    /// Decodes `input_file`, crops it and encodes the result in the output format.
+    fn crop_and_encode(&self, input_file: &Path, plan: Option<&CropInfo>) -> Result<Encoded> {
+        // 拡張子が無い・誤っているファイルもあるので中身から形式を判定する
+        let reader = image::ImageReader::open(input_file)?.with_guessed_format()?;
+        let format = if self.in_place {
+            // 元のファイルを置き換えるので入力と同じ形式でしか書けない
+            reader
+                .format()
+                .filter(image::ImageFormat::writing_enabled)
+                .ok_or_else(|| {
+                    Error::Unsupported(format!(
+                        "cannot write {} in place in its own format",
+                        input_file.display()
+                    ))
+                })?
+        } else {
+            self.output_format.resolve(reader.format())
+        };
+        self.crop_and_encode_from(reader, format, plan)
+    }
+
+    /// Decodes the image behind `reader`, crops it, following `plan` if given, and encodes
+    /// the result as `format`.
+    fn crop_and_encode_from<R: BufRead + Seek>(
+        &self,
+        reader: image::ImageReader<R>,
+        format: image::ImageFormat,
+        plan: Option<&CropInfo>,
+    ) -> Result<Encoded> {
+        let img = reader.decode()?;
+        let (cropped_img, crop) = match plan {
+            Some(plan) => (self.cropper.apply(&img, plan)?, *plan),
+            None => self.cropper.crop_with_info(&img)?,
+        };
+        let bytes = if self.dry_run {
+            Vec::new()
+        } else {
+            encode_image(&cropped_img, format, &self.encoder_options)?
+        };
+        Ok(Encoded {
+            bytes,
+            format,
+            crop,
+        })
+    }
+
+    /// Writes `encoded` to `output_file` unless the overwrite policy keeps an existing file.
+    ///
+    /// Fails if `output_file` is `input_file` itself; only in-place mode replaces inputs.
+    fn write_output(
+        &self,
+        input_file: &Path,
+        output_file: &Path,
+        encoded: &Encoded,
+    ) -> Result<Outcome> {
+        if is_same_file(input_file, output_file) {
+            return Err(Error::OutputIsInput(output_file.to_path_buf()));
+        }
+        if !self.overwrite.allows(input_file, output_file)? {
+            return Ok(Outcome::Skipped(output_file.to_path_buf()));
+        }
+        if self.dry_run {
+            return Ok(Outcome::WouldWrite(output_file.to_path_buf()));
+        }
+        if let Some(dir) = output_file.parent() {
+            // 再帰モードの出力先やテンプレート中のサブディレクトリを作る
+            std::fs::create_dir_all(dir)?;
+        }
+        if self.overwrite == OverwritePolicy::Never {
+            // 確認後に別のワーカーが同じファイルを作った場合も上書きしない
+            if !write_atomic_new(output_file, &encoded.bytes)? {
+                return Ok(Outcome::Skipped(output_file.to_path_buf()));
+            }
+        } else {
+            write_atomic(output_file, &encoded.bytes, self.backup)?;
+        }
+        self.write_sidecar(output_file, encoded)?;
+        Ok(Outcome::Written(output_file.to_path_buf()))
+    }
+
+    /// Writes the sidecar metadata for the image just written to `output_file`, if enabled.
+    fn write_sidecar(&self, output_file: &Path, encoded: &Encoded) -> Result<()> {
+        match self.sidecar {
+            Some(format) => write_sidecar(output_file, &encoded.crop, format),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Decodes the image at `path`, detecting its format from the content.
+fn decode(path: &Path) -> Result<DynamicImage> {
+    Ok(image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()?)
+}
+
+/// An input file and the directory its output goes to.
+struct Job {
+    input: PathBuf,
+    output_dir: PathBuf,
+}
+
+impl Job {
+    /// A file found at `relative` below `root`, mirrored to the same place below `output_dir`.
+    fn new(root: &Path, relative: &Path, output_dir: &Path) -> Self {
+        Self {
+            input: root.join(relative),
+            output_dir: match relative.parent() {
+                Some(parent) => output_dir.join(parent),
+                None => output_dir.to_path_buf(),
+            },
+        }
+    }
+}
+
+/// A cropped image encoded in its output format.
+struct Encoded {
+    bytes: Vec<u8>,
+    format: image::ImageFormat,
+    crop: CropInfo,
+}
+
+/// Returns where output goes when no output location is given for `input`.
+///
+/// - a directory writes into its `output` subdirectory
+/// - a file writes into the `output` subdirectory of its parent directory, like a directory
+///   of files would
+/// - a glob pattern writes into `output` under the directory its pattern starts from
+pub fn default_output_dir(input: &Path) -> Result<PathBuf> {
+    if input.is_dir() {
+        return Ok(input.join("output"));
+    }
+    if input.is_file() {
+        return Ok(match input.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.join("output"),
+            _ => PathBuf::from("output"),
+        });
+    }
+    if !is_glob(input) {
+        return Err(Error::NotFound(input.to_path_buf()));
+    }
+    Ok(glob_base(input).join("output"))
+}
+
+/// Whether `a` and `b` name the same existing file, after resolving symlinks and `..`.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Builder for [`Pipeline`].
+#[derive(Debug, Clone)]
+pub struct PipelineBuilder {
+    pipeline: Pipeline,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    group_patterns: Vec<String>,
+    group_by: GroupBy,
+}
+
+impl PipelineBuilder {
+    /// Only process files whose path relative to the input directory matches one of the
+    /// include globs. May be called repeatedly; with no include globs every image is processed.
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Skip files whose path relative to the input directory matches this glob. May be
+    /// called repeatedly.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Enables walking subdirectories in [`Pipeline::process_directory`]. Disabled by default.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.pipeline.recursive = recursive;
+        self
+    }
+
+    /// Sets the format cropped images are written in. Defaults to PNG.
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.pipeline.output_format = output_format;
+        self
+    }
+
+    /// Sets the encoder settings used when writing cropped images.
+    pub fn encoder_options(mut self, encoder_options: EncoderOptions) -> Self {
+        self.pipeline.encoder_options = encoder_options;
+        self
+    }
+
+    /// Sets the template for output file names. Defaults to `{stem}_cropped.{ext}`.
+    pub fn name_template(mut self, name_template: NameTemplate) -> Self {
+        self.pipeline.name_template = name_template;
+        self
+    }
+
+    /// Sets what happens when an output file already exists. Defaults to
+    /// [`OverwritePolicy::Always`].
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.pipeline.overwrite = overwrite;
+        self
+    }
+
+    /// Still decodes and crops files whose existing output the overwrite policy keeps, so
+    /// that their reports carry a [`CropInfo`], e.g. for [`write_manifest`].
+    ///
+    /// Disabled by default: when the output name does not depend on the cropped image, such
+    /// files are skipped before they are decoded.
+    ///
+    /// [`write_manifest`]: crate::write_manifest
+    pub fn crop_skipped(mut self, crop_skipped: bool) -> Self {
+        self.pipeline.crop_skipped = crop_skipped;
+        self
+    }
+
+    /// Replaces each input file with its cropped version, in the input's own format.
+    /// Output directories, name templates and the output format are ignored.
+    pub fn in_place(mut self, in_place: bool) -> Self {
+        self.pipeline.in_place = in_place;
+        self
+    }
+
+    /// Copies a file that is about to be replaced to `<file>.bak` first.
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.pipeline.backup = backup;
+        self
+    }
+
+    /// Stops starting new files in a batch once one has failed. Files already in progress
+    /// still finish. Disabled by default, so every file is attempted.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.pipeline.fail_fast = fail_fast;
+        self
+    }
+
+    /// Computes crop boxes and output paths without encoding or writing anything.
+    ///
+    /// Files that would be written are reported as [`Outcome::WouldWrite`]. Since nothing
+    /// is encoded, `{hash}` in the name template renders as zeros.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.pipeline.dry_run = dry_run;
+        self
+    }
+
+    /// Flags files in [`FileReport::flagged`] whose crop removes more than this percentage
+    /// of the original area. Not set by default.
+    pub fn max_area_loss(mut self, percent: f64) -> Self {
+        self.pipeline.max_area_loss = Some(percent);
+        self
+    }
+
+    /// Writes a sidecar file next to every written image recording where the cropped image
+    /// sat in the original canvas, in TexturePacker's `spriteSourceSize`/`sourceSize` layout.
+    /// The sidecar is named after the image with `.json` or `.toml` appended, e.g.
+    /// `a_cropped.png.json`. Disabled by default.
+    pub fn sidecar(mut self, format: Option<SidecarFormat>) -> Self {
+        self.pipeline.sidecar = format;
+        self
+    }
+
+    /// Crops the files whose name matches this glob, such as `walk_*.png`, with one shared
+    /// rectangle per directory, so that animation frames line up. May be called repeatedly
+    /// to define several groups; takes precedence over [`PipelineBuilder::group_by`].
+    pub fn group(mut self, pattern: impl Into<String>) -> Self {
+        self.group_patterns.push(pattern.into());
+        self
+    }
+
+    /// Sets how the remaining files are grouped for a shared crop rectangle. Defaults to
+    /// [`GroupBy::None`].
+    ///
+    /// A group is cropped to the union of its members' trim boxes, followed by the aspect
+    /// correction chosen for its first file. Members must all have the same size.
+    pub fn group_by(mut self, group_by: GroupBy) -> Self {
+        self.group_by = group_by;
+        self
+    }
+
+    /// Builds the configured [`Pipeline`], failing if a glob pattern is invalid or the
+    /// cropper's aspect range is inverted.
+    pub fn build(mut self) -> Result<Pipeline> {
+        self.pipeline.cropper.aspect().validate()?;
+        self.pipeline.filter = FileFilter::new(&self.include, &self.exclude)?;
+        self.pipeline.grouping = Grouping::new(&self.group_patterns, self.group_by)?;
+        Ok(self.pipeline)
+    }
+}
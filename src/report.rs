@@ -0,0 +1,192 @@
+use crate::cropper::CropBox;
+use crate::error::{Error, Result};
+use crate::pipeline::{FileReport, Outcome};
+use crate::trim::TrimMode;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Layout of a machine-readable report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// A single JSON array.
+    #[default]
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl ReportFormat {
+    /// Guesses the format from a report file name: `.ndjson` and `.jsonl` mean
+    /// [`ReportFormat::Ndjson`], anything else [`ReportFormat::Json`].
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson") | Some("jsonl") => ReportFormat::Ndjson,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ReportFormat::Ndjson),
+            _ => Err(Error::Parse(format!(
+                "invalid report format '{}', expected json or ndjson",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    input: &'a Path,
+    output: Option<&'a Path>,
+    status: &'static str,
+    original: Option<Size>,
+    trim_mode: Option<TrimMode>,
+    trim: Option<CropBox>,
+    margin: Option<CropBox>,
+    aspect_crop: Option<CropBox>,
+    #[serde(rename = "final")]
+    final_size: Option<Size>,
+    removed_area_percent: Option<f64>,
+    flagged: bool,
+    duration_ms: f64,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Size {
+    width: u32,
+    height: u32,
+}
+
+impl From<(u32, u32)> for Size {
+    fn from((width, height): (u32, u32)) -> Self {
+        Self { width, height }
+    }
+}
+
+impl<'a> From<&'a FileReport> for Entry<'a> {
+    fn from(report: &'a FileReport) -> Self {
+        let (output, status, error) = match &report.result {
+            Ok(Outcome::Written(path)) => (Some(path.as_path()), "written", None),
+            Ok(Outcome::Skipped(path)) => (Some(path.as_path()), "skipped", None),
+            Ok(Outcome::WouldWrite(path)) => (Some(path.as_path()), "dry-run", None),
+            Ok(Outcome::NotAttempted) => (None, "not-attempted", None),
+            Err(e) => (None, "failed", Some(e.to_string())),
+        };
+        Self {
+            input: &report.input,
+            output,
+            status,
+            original: report.crop.map(|crop| crop.original_size.into()),
+            trim_mode: report.crop.map(|crop| crop.trim_mode),
+            trim: report.crop.map(|crop| crop.trim),
+            margin: report.crop.and_then(|crop| crop.margin),
+            aspect_crop: report.crop.and_then(|crop| crop.aspect_crop),
+            final_size: report.crop.map(|crop| crop.final_size.into()),
+            removed_area_percent: report.crop.map(|crop| crop.removed_area_percent()),
+            flagged: report.flagged,
+            duration_ms: report.duration.as_secs_f64() * 1000.0,
+            error,
+        }
+    }
+}
+
+/// Writes one entry per file in `reports` to `writer`.
+///
+/// Each entry records the input and output paths, a `status` of `written`, `skipped`,
+/// `dry-run`, `failed` or `not-attempted` (fail-fast stopped before the file started), the
+/// original size, the `trim_mode` that was applied (`none`, `alpha` or `background`; images
+/// without alpha may differ from the configured one), the trim, margin and aspect-crop
+/// boxes in original image coordinates, the final size, the percentage of the area removed
+/// and whether that was flagged, the duration in milliseconds and the error message, if
+/// any.
+///
+/// The crop fields are null for files that were never cropped: failures and files skipped
+/// before decoding because the overwrite policy kept their output.
+pub fn write_report(
+    reports: &[FileReport],
+    format: ReportFormat,
+    mut writer: impl Write,
+) -> Result<()> {
+    let entries: Vec<Entry> = reports.iter().map(Entry::from).collect();
+    match format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &entries).map_err(io::Error::from)?;
+            writeln!(writer)?;
+        }
+        ReportFormat::Ndjson => {
+            for entry in &entries {
+                serde_json::to_writer(&mut writer, entry).map_err(io::Error::from)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Counts of what happened to the files in a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Summary {
+    /// Files whose output was written.
+    pub written: usize,
+    /// Files skipped because of the overwrite policy.
+    pub skipped: usize,
+    /// Files a dry run would have written.
+    pub would_write: usize,
+    /// Files that could not be processed.
+    pub failed: usize,
+    /// Files fail-fast mode stopped before they were started.
+    pub not_attempted: usize,
+}
+
+impl Summary {
+    /// Tallies the results in `reports`.
+    pub fn from_reports(reports: &[FileReport]) -> Self {
+        let mut summary = Self::default();
+        for report in reports {
+            match report.result {
+                Ok(Outcome::Written(_)) => summary.written += 1,
+                Ok(Outcome::Skipped(_)) => summary.skipped += 1,
+                Ok(Outcome::WouldWrite(_)) => summary.would_write += 1,
+                Ok(Outcome::NotAttempted) => summary.not_attempted += 1,
+                Err(_) => summary.failed += 1,
+            }
+        }
+        summary
+    }
+
+    /// Total number of files that were attempted.
+    pub fn processed(&self) -> usize {
+        self.written + self.skipped + self.would_write + self.failed
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} processed: {} written, ",
+            self.processed(),
+            self.written
+        )?;
+        if self.would_write > 0 {
+            write!(f, "{} would be written, ", self.would_write)?;
+        }
+        write!(f, "{} skipped, {} failed", self.skipped, self.failed)?;
+        if self.not_attempted > 0 {
+            write!(f, ", {} not attempted", self.not_attempted)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::cropper::CropInfo;
+use crate::error::{Error, Result};
+use crate::pipeline::{FileReport, Outcome};
+use crate::write::write_atomic;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// File format of sidecar metadata and manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SidecarFormat {
+    /// Pretty-printed JSON.
+    #[default]
+    Json,
+    /// TOML.
+    Toml,
+}
+
+impl SidecarFormat {
+    /// Guesses the format from a file name: `.toml` means [`SidecarFormat::Toml`], anything
+    /// else [`SidecarFormat::Json`].
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => SidecarFormat::Toml,
+            _ => SidecarFormat::Json,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "json",
+            SidecarFormat::Toml => "toml",
+        }
+    }
+
+    fn serialize(self, value: &impl Serialize) -> Result<Vec<u8>> {
+        let text = match self {
+            SidecarFormat::Json => serde_json::to_string_pretty(value).map_err(io::Error::from)?,
+            SidecarFormat::Toml => toml::to_string_pretty(value)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        };
+        Ok((text + "\n").into_bytes())
+    }
+}
+
+impl FromStr for SidecarFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(SidecarFormat::Json),
+            "toml" => Ok(SidecarFormat::Toml),
+            _ => Err(Error::Parse(format!(
+                "invalid sidecar format '{}', expected json or toml",
+                s
+            ))),
+        }
+    }
+}
+
+/// Placement of a cropped image in its original canvas, laid out like a frame in a
+/// TexturePacker JSON hash.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpriteFrame {
+    frame: Rect,
+    rotated: bool,
+    trimmed: bool,
+    sprite_source_size: Rect,
+    source_size: Size,
+}
+
+#[derive(Debug, Serialize)]
+struct Rect {
+    x: i64,
+    y: i64,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+impl From<&CropInfo> for SpriteFrame {
+    fn from(crop: &CropInfo) -> Self {
+        let (width, height) = crop.final_size;
+        let (x, y) = crop.source_offset();
+        Self {
+            frame: Rect {
+                x: 0,
+                y: 0,
+                w: width,
+                h: height,
+            },
+            rotated: false,
+            trimmed: (x, y, width, height) != (0, 0, crop.original_size.0, crop.original_size.1),
+            sprite_source_size: Rect {
+                x,
+                y,
+                w: width,
+                h: height,
+            },
+            source_size: Size {
+                w: crop.original_size.0,
+                h: crop.original_size.1,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    frames: BTreeMap<String, SpriteFrame>,
+    meta: Meta,
+}
+
+#[derive(Debug, Serialize)]
+struct Meta {
+    app: &'static str,
+    version: &'static str,
+}
+
+/// Returns the sidecar path for the image at `output`: its full name with the extension of
+/// `format` appended, e.g. `a_cropped.png.json`.
+pub(crate) fn sidecar_path(output: &Path, format: SidecarFormat) -> PathBuf {
+    // 拡張子を置き換えると a.png と a.bmp の出力が同じサイドカーを取り合う
+    let mut name = output.as_os_str().to_owned();
+    name.push(".");
+    name.push(format.extension());
+    PathBuf::from(name)
+}
+
+/// Writes the sprite placement of `crop` next to the image at `output`.
+pub(crate) fn write_sidecar(output: &Path, crop: &CropInfo, format: SidecarFormat) -> Result<()> {
+    let bytes = format.serialize(&SpriteFrame::from(crop))?;
+    write_atomic(&sidecar_path(output, format), &bytes, false)
+}
+
+/// Writes one manifest at `path` with the sprite placement of every output in `reports`,
+/// including those the overwrite policy kept, in the format guessed from the extension
+/// of `path`.
+///
+/// Frames are keyed by output path, relative to the manifest's directory when possible.
+/// Each frame has TexturePacker's `frame`, `rotated`, `trimmed`, `spriteSourceSize` and
+/// `sourceSize` fields; `spriteSourceSize.x`/`y` is where the cropped image's top-left
+/// corner sits in the original canvas, negative where padding was added.
+pub fn write_manifest(reports: &[FileReport], path: &Path) -> Result<()> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    let frames = reports
+        .iter()
+        .filter_map(|report| match (&report.result, &report.crop) {
+            (Ok(Outcome::Written(output) | Outcome::Skipped(output)), Some(crop)) => {
+                let name = output.strip_prefix(base).unwrap_or(output);
+                Some((name.display().to_string(), SpriteFrame::from(crop)))
+            }
+            _ => None,
+        })
+        .collect();
+    let manifest = Manifest {
+        frames,
+        meta: Meta {
+            app: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        },
+    };
+    let bytes = SidecarFormat::from_path(path).serialize(&manifest)?;
+    write_atomic(path, &bytes, false)
+}
@@ -0,0 +1,278 @@
+use crate::color::{euclidean, Color, ColorSpace};
+use crate::error::Error;
+use image::{DynamicImage, GenericImageView};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// What to treat as empty border when trimming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimMode {
+    /// Do not trim.
+    None,
+    /// Trim transparent pixels.
+    #[default]
+    Alpha,
+    /// Trim pixels close to a solid background color.
+    Background,
+}
+
+impl FromStr for TrimMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(TrimMode::None),
+            "alpha" => Ok(TrimMode::Alpha),
+            "background" => Ok(TrimMode::Background),
+            _ => Err(Error::Parse(format!(
+                "invalid trim mode '{}', expected none, alpha or background",
+                s
+            ))),
+        }
+    }
+}
+
+/// What [`TrimMode::Alpha`] does with images that have no alpha channel, such as RGB
+/// JPEGs, where every pixel is opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpaquePolicy {
+    /// Leave the image untrimmed.
+    #[default]
+    Skip,
+    /// Trim a solid background color instead, as with [`TrimMode::Background`].
+    Background,
+}
+
+impl FromStr for OpaquePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(OpaquePolicy::Skip),
+            "background" => Ok(OpaquePolicy::Background),
+            _ => Err(Error::Parse(format!(
+                "invalid opaque policy '{}', expected skip or background",
+                s
+            ))),
+        }
+    }
+}
+
+/// Crops away the transparent border of `img`.
+///
+/// Pixels whose alpha is at or below `alpha_threshold` count as transparent. The
+/// threshold is given on the 8-bit scale and is rescaled for 16-bit and float images.
+pub fn crop_transparent_edges(img: &DynamicImage, alpha_threshold: u8) -> DynamicImage {
+    crop_to_bounds(img, transparent_bounds(img, alpha_threshold))
+}
+
+/// Returns the `(left, top, right, bottom)` box that [`crop_transparent_edges`] keeps.
+/// `right` and `bottom` are exclusive.
+pub fn transparent_bounds(img: &DynamicImage, alpha_threshold: u8) -> (u32, u32, u32, u32) {
+    let (width, height) = img.dimensions();
+    let threshold16 = u16::from(alpha_threshold) * 257;
+    let threshold32 = f32::from(alpha_threshold) / 255.0;
+    match img {
+        DynamicImage::ImageLumaA8(buf) => {
+            alpha_bounds::<_, 2>(buf.as_raw(), width, height, alpha_threshold)
+        }
+        DynamicImage::ImageRgba8(buf) => {
+            alpha_bounds::<_, 4>(buf.as_raw(), width, height, alpha_threshold)
+        }
+        DynamicImage::ImageLumaA16(buf) => {
+            alpha_bounds::<_, 2>(buf.as_raw(), width, height, threshold16)
+        }
+        DynamicImage::ImageRgba16(buf) => {
+            alpha_bounds::<_, 4>(buf.as_raw(), width, height, threshold16)
+        }
+        DynamicImage::ImageRgba32F(buf) => {
+            alpha_bounds::<_, 4>(buf.as_raw(), width, height, threshold32)
+        }
+        // アルファを持たない画像は全画素が不透明なので走査しない
+        _ if !img.color().has_alpha() => (0, 0, width, height),
+        _ => alpha_bounds::<_, 4>(img.to_rgba8().as_raw(), width, height, alpha_threshold),
+    }
+}
+
+/// Scans interleaved samples with `C` channels, alpha last, and returns the bounds of the
+/// pixels whose alpha is above `threshold`, like [`content_bounds`].
+///
+/// All passes walk the buffer row by row. The top and bottom passes stop at the first row
+/// with content; the left and right passes then only look at the part of each remaining
+/// row outside the bounds found so far.
+fn alpha_bounds<T: Copy + PartialOrd, const C: usize>(
+    samples: &[T],
+    width: u32,
+    height: u32,
+    threshold: T,
+) -> (u32, u32, u32, u32) {
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        return (0, 0, width, height);
+    }
+    let stride = w * C;
+    let rows = &samples[..stride * h];
+    let is_opaque = |pixel: &[T]| pixel[C - 1] > threshold;
+    let row_has_content = |row: &[T]| any_opaque::<T, C>(row, threshold);
+
+    let Some(top) = rows.chunks_exact(stride).position(row_has_content) else {
+        return (0, 0, width, height);
+    };
+    let bottom = h - rows
+        .chunks_exact(stride)
+        .rev()
+        .position(row_has_content)
+        .unwrap_or(0);
+
+    let mut left = w;
+    let mut right = 0;
+    for row in rows[top * stride..bottom * stride].chunks_exact(stride) {
+        if let Some(x) = row[..left * C].chunks_exact(C).position(is_opaque) {
+            left = x;
+        }
+        if let Some(x) = row[right * C..].chunks_exact(C).rposition(is_opaque) {
+            right += x + 1;
+        }
+        if left == 0 && right == w {
+            break;
+        }
+    }
+    (left as u32, top as u32, right as u32, bottom as u32)
+}
+
+/// Whether any pixel in `row` has alpha above `threshold`.
+///
+/// Pixels are compared in fixed-size blocks without branching inside a block, so the
+/// comparison can be vectorised; the scan still stops after the first block with content.
+fn any_opaque<T: Copy + PartialOrd, const C: usize>(row: &[T], threshold: T) -> bool {
+    const BLOCK: usize = 64;
+    let (pixels, _) = row.as_chunks::<C>();
+    pixels.chunks(BLOCK).any(|block| {
+        block
+            .iter()
+            .fold(false, |found, pixel| found | (pixel[C - 1] > threshold))
+    })
+}
+
+/// Crops away the border of `img` whose color is within `tolerance` of `background`.
+///
+/// When `background` is `None` it is detected with [`detect_background`]. Alpha is ignored.
+pub fn crop_background_edges(
+    img: &DynamicImage,
+    background: Option<Color>,
+    tolerance: f32,
+    space: ColorSpace,
+) -> DynamicImage {
+    crop_to_bounds(img, background_bounds(img, background, tolerance, space))
+}
+
+/// Returns the `(left, top, right, bottom)` box that [`crop_background_edges`] keeps.
+/// `right` and `bottom` are exclusive.
+pub fn background_bounds(
+    img: &DynamicImage,
+    background: Option<Color>,
+    tolerance: f32,
+    space: ColorSpace,
+) -> (u32, u32, u32, u32) {
+    let background = background.unwrap_or_else(|| detect_background(img, tolerance, space));
+    let background = space.coordinates(background);
+    let is_content = |x, y| {
+        let pixel = pixel_color(img, x, y);
+        euclidean(space.coordinates(pixel), background) > tolerance
+    };
+    content_bounds(img.width(), img.height(), is_content)
+}
+
+/// Crops `img` to a `(left, top, right, bottom)` box.
+pub(crate) fn crop_to_bounds(img: &DynamicImage, bounds: (u32, u32, u32, u32)) -> DynamicImage {
+    let (left, top, right, bottom) = bounds;
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// Guesses the background color of `img` from its four corners.
+///
+/// The corner that agrees, within `tolerance`, with the most other corners wins; ties go to
+/// the top-left corner first and then clockwise.
+pub fn detect_background(img: &DynamicImage, tolerance: f32, space: ColorSpace) -> Color {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Color::WHITE;
+    }
+    let corners = [
+        pixel_color(img, 0, 0),
+        pixel_color(img, width - 1, 0),
+        pixel_color(img, width - 1, height - 1),
+        pixel_color(img, 0, height - 1),
+    ];
+    let votes = |c: Color| {
+        corners
+            .iter()
+            .filter(|&&other| space.distance(c, other) <= tolerance)
+            .count()
+    };
+    // max_by_key は同点のとき最後の要素を返すので、先頭を優先するため逆順に走査する
+    corners
+        .into_iter()
+        .rev()
+        .max_by_key(|&c| votes(c))
+        .unwrap_or(Color::WHITE)
+}
+
+fn pixel_color(img: &DynamicImage, x: u32, y: u32) -> Color {
+    let p = img.get_pixel(x, y);
+    Color::new(p[0], p[1], p[2])
+}
+
+/// Scans the four edges inwards and returns the `(left, top, right, bottom)` bounds of the
+/// pixels for which `is_content` holds. `right` and `bottom` are exclusive.
+///
+/// If no pixel is content the full image bounds are returned.
+pub(crate) fn content_bounds(
+    width: u32,
+    height: u32,
+    is_content: impl Fn(u32, u32) -> bool,
+) -> (u32, u32, u32, u32) {
+    let mut top = 0;
+    let mut bottom = height;
+    let mut left = 0;
+    let mut right = width;
+
+    'outer: for y in 0..height {
+        for x in 0..width {
+            if is_content(x, y) {
+                top = y;
+                break 'outer;
+            }
+        }
+    }
+
+    'outer: for y in (0..height).rev() {
+        for x in 0..width {
+            if is_content(x, y) {
+                bottom = y + 1;
+                break 'outer;
+            }
+        }
+    }
+
+    'outer: for x in 0..width {
+        for y in top..bottom {
+            if is_content(x, y) {
+                left = x;
+                break 'outer;
+            }
+        }
+    }
+
+    'outer: for x in (0..width).rev() {
+        for y in top..bottom {
+            if is_content(x, y) {
+                right = x + 1;
+                break 'outer;
+            }
+        }
+    }
+
+    (left, top, right, bottom)
+}
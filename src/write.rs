@@ -0,0 +1,110 @@
+use crate::error::{Error, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+/// What to do when an output file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Keep the existing file and skip the input.
+    Never,
+    /// Replace the existing file.
+    #[default]
+    Always,
+    /// Replace the existing file only if the input was modified after it.
+    IfNewer,
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "never" => Ok(OverwritePolicy::Never),
+            "always" => Ok(OverwritePolicy::Always),
+            "if-newer" => Ok(OverwritePolicy::IfNewer),
+            _ => Err(Error::Parse(format!(
+                "invalid overwrite policy '{}', expected never, always or if-newer",
+                s
+            ))),
+        }
+    }
+}
+
+impl OverwritePolicy {
+    /// Whether `output` may be written for `input` under this policy.
+    pub fn allows(self, input: &Path, output: &Path) -> Result<bool> {
+        let existing = match fs::metadata(output) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e.into()),
+        };
+        match self {
+            OverwritePolicy::Never => Ok(false),
+            OverwritePolicy::Always => Ok(true),
+            OverwritePolicy::IfNewer => Ok(fs::metadata(input)?.modified()? > existing.modified()?),
+        }
+    }
+}
+
+/// Writes `bytes` to `path` through a temporary file in the same directory and a rename,
+/// so that readers never observe a partially written file.
+///
+/// The data is flushed to disk before the rename, and a replaced file keeps its
+/// permissions. With `backup` set, an existing file at `path` is first copied to
+/// `<path>.bak`.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8], backup: bool) -> Result<()> {
+    let temp = write_temp(path, bytes)?;
+    if backup && path.exists() {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(".bak");
+        fs::copy(path, backup_path)?;
+    }
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Like [`write_atomic`], but never replaces an existing file, even one created while the
+/// data was being written. Returns whether the file was written.
+pub(crate) fn write_atomic_new(path: &Path, bytes: &[u8]) -> Result<bool> {
+    let temp = write_temp(path, bytes)?;
+    match temp.persist_noclobber(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e.error.into()),
+    }
+}
+
+/// Writes `bytes` to a uniquely named temporary file next to `path` and syncs it to disk.
+///
+/// The file gets the permissions of an existing file at `path`, or the defaults for a new
+/// file otherwise. It is removed again if it is dropped without being persisted.
+fn write_temp(path: &Path, bytes: &[u8]) -> Result<NamedTempFile> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidPath(path.to_path_buf()))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut prefix = OsString::from(".");
+    prefix.push(file_name);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(".tmp");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // tempfile の既定は 0600 なので、通常のファイルと同じく umask に任せる
+        builder.permissions(fs::Permissions::from_mode(0o666));
+    }
+    let mut temp = builder.tempfile_in(dir)?;
+    temp.write_all(bytes)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+    temp.as_file().sync_all()?;
+    Ok(temp)
+}
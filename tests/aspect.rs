@@ -0,0 +1,195 @@
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage, Rgba, RgbaImage};
+use image_cropper::{
+    aspect_crop_size, aspect_crop_window, crop_to_aspect_ratio, Anchor, AspectConstraint, Cropper,
+    Pipeline,
+};
+use proptest::prelude::*;
+
+/// Whether some whole-pixel length of the shrinking side puts the ratio inside the bounds.
+fn feasible(width: u32, height: u32, min: f32, max: f32) -> bool {
+    let (w, h) = (f64::from(width), f64::from(height));
+    let (min, max) = (f64::from(min), f64::from(max));
+    if w / h < min {
+        (w / max).ceil().max(1.0) <= (w / min).floor().min(h)
+    } else if w / h > max {
+        (h * min).ceil().max(1.0) <= (h * max).floor().min(w)
+    } else {
+        true
+    }
+}
+
+fn ratio_bounds() -> impl Strategy<Value = (f32, f32)> {
+    (0.05f32..20.0, 1.0f32..4.0).prop_map(|(min, spread)| (min, min * spread))
+}
+
+proptest! {
+    #[test]
+    fn crop_size_fits_inside_input(
+        width in 1u32..5000,
+        height in 1u32..5000,
+        (min, max) in ratio_bounds(),
+    ) {
+        let (new_width, new_height) = aspect_crop_size(width, height, min, max);
+        prop_assert!(new_width >= 1 && new_width <= width);
+        prop_assert!(new_height >= 1 && new_height <= height);
+        // 削られるのは片側の辺だけ
+        prop_assert!(new_width == width || new_height == height);
+    }
+
+    #[test]
+    fn crop_size_lands_within_bounds(
+        width in 1u32..5000,
+        height in 1u32..5000,
+        (min, max) in ratio_bounds(),
+    ) {
+        prop_assume!(feasible(width, height, min, max));
+        let (new_width, new_height) = aspect_crop_size(width, height, min, max);
+        let ratio = f64::from(new_width) / f64::from(new_height);
+        prop_assert!(ratio >= f64::from(min) * (1.0 - 1e-6), "{} < {}", ratio, min);
+        prop_assert!(ratio <= f64::from(max) * (1.0 + 1e-6), "{} > {}", ratio, max);
+    }
+
+    #[test]
+    fn crop_size_keeps_images_already_in_range(
+        width in 1u32..5000,
+        height in 1u32..5000,
+        below in 1.0f32..4.0,
+        above in 1.0f32..4.0,
+    ) {
+        let ratio = width as f32 / height as f32;
+        let (min, max) = (ratio / below, ratio * above);
+        prop_assert_eq!(aspect_crop_size(width, height, min, max), (width, height));
+    }
+
+    #[test]
+    fn exact_ratio_is_within_one_pixel(
+        width in 1u32..5000,
+        height in 1u32..5000,
+        ratio in 0.05f32..20.0,
+    ) {
+        let (new_width, new_height) = aspect_crop_size(width, height, ratio, ratio);
+        let ratio = f64::from(ratio);
+        if new_height < height {
+            prop_assert!((f64::from(new_width) / ratio - f64::from(new_height)).abs() <= 1.0);
+        } else if new_width < width {
+            prop_assert!((f64::from(new_height) * ratio - f64::from(new_width)).abs() <= 1.0);
+        }
+    }
+}
+
+#[test]
+fn narrow_images_lose_height() {
+    // 以前は幅を広げようとして減算がアンダーフローしていた
+    let img = DynamicImage::ImageRgba8(RgbaImage::new(10, 1000));
+    let cropped = crop_to_aspect_ratio(img, 2.0 / 5.0, 5.0 / 2.0);
+    assert_eq!(cropped.dimensions(), (10, 25));
+}
+
+#[test]
+fn wide_images_lose_width() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::new(1000, 10));
+    let cropped = crop_to_aspect_ratio(img, 2.0 / 5.0, 5.0 / 2.0);
+    assert_eq!(cropped.dimensions(), (25, 10));
+}
+
+fn crop(width: u32, height: u32, min: f32, max: f32) -> (u32, u32) {
+    let img = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+    crop_to_aspect_ratio(img, min, max).dimensions()
+}
+
+#[test]
+fn images_exactly_at_the_bounds_are_kept() {
+    assert_eq!(crop(200, 500, 2.0 / 5.0, 5.0 / 2.0), (200, 500));
+    assert_eq!(crop(500, 200, 2.0 / 5.0, 5.0 / 2.0), (500, 200));
+    assert_eq!(crop(2, 5, 2.0 / 5.0, 5.0 / 2.0), (2, 5));
+    assert_eq!(crop(5, 2, 2.0 / 5.0, 5.0 / 2.0), (5, 2));
+}
+
+#[test]
+fn one_pixel_past_the_bounds_is_cropped_back_inside() {
+    // 199/500 は 2:5 をわずかに下回るので高さを削る。498 では 0.3996 で範囲外になる
+    assert_eq!(crop(199, 500, 2.0 / 5.0, 5.0 / 2.0), (199, 497));
+    assert_eq!(crop(500, 199, 2.0 / 5.0, 5.0 / 2.0), (497, 199));
+    assert_eq!(crop(201, 500, 2.0 / 5.0, 5.0 / 2.0), (201, 500));
+}
+
+#[test]
+fn exact_ratio_is_kept_when_already_met() {
+    assert_eq!(crop(1920, 1080, 16.0 / 9.0, 16.0 / 9.0), (1920, 1080));
+    assert_eq!(crop(1080, 1920, 9.0 / 16.0, 9.0 / 16.0), (1080, 1920));
+    assert_eq!(crop(300, 300, 1.0, 1.0), (300, 300));
+}
+
+#[test]
+fn exact_ratio_crops_a_single_extra_pixel() {
+    assert_eq!(crop(1921, 1080, 16.0 / 9.0, 16.0 / 9.0), (1920, 1080));
+    assert_eq!(crop(1920, 1081, 16.0 / 9.0, 16.0 / 9.0), (1920, 1080));
+    assert_eq!(crop(301, 300, 1.0, 1.0), (300, 300));
+    assert_eq!(crop(300, 301, 1.0, 1.0), (300, 300));
+}
+
+#[test]
+fn single_pixel_lines_keep_one_pixel() {
+    assert_eq!(crop(1, 1000, 2.0 / 5.0, 5.0 / 2.0), (1, 2));
+    assert_eq!(crop(1000, 1, 2.0 / 5.0, 5.0 / 2.0), (2, 1));
+    assert_eq!(crop(1, 1, 2.0, 3.0), (1, 1));
+}
+
+#[test]
+fn inverted_ranges_are_rejected() {
+    assert!(AspectConstraint::range(3.0, 1.0).is_err());
+    assert_eq!(
+        AspectConstraint::range(1.0, 1.0).unwrap(),
+        AspectConstraint::Range { min: 1.0, max: 1.0 }
+    );
+    let cropper = Cropper::builder().aspect_range(3.0, 1.0).build();
+    assert!(Pipeline::builder(cropper).build().is_err());
+}
+
+/// A transparent 100x10 image with opaque columns in each `start..end` of `blocks`.
+fn with_columns(blocks: &[(u32, u32)]) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(100, 10, |x, _| {
+        if blocks.iter().any(|&(start, end)| (start..end).contains(&x)) {
+            Rgba([255, 255, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    }))
+}
+
+/// Left edge of the 25-pixel window that the auto anchor keeps from a 100x10 image.
+fn auto_left(img: &DynamicImage) -> u32 {
+    let (left, top, width, height) = aspect_crop_window(img, 2.0 / 5.0, 5.0 / 2.0, Anchor::Auto);
+    assert_eq!((top, width, height), (0, 25, 10));
+    left
+}
+
+#[test]
+fn auto_anchor_keeps_the_densest_window() {
+    assert_eq!(auto_left(&with_columns(&[(0, 10)])), 0);
+    assert_eq!(auto_left(&with_columns(&[(90, 100)])), 75);
+    // 20 列の塊を丸ごと含む窓のうち中央に最も近いもの
+    assert_eq!(auto_left(&with_columns(&[(5, 10), (60, 80)])), 55);
+}
+
+#[test]
+fn auto_anchor_ties_keep_the_window_closest_to_the_centre() {
+    // 全面透明なら中央寄せと同じ
+    assert_eq!(auto_left(&with_columns(&[])), 37);
+    // どちらの塊を含む窓も同じ重さなので、中央 (37) に近い 60 を選ぶ
+    assert_eq!(auto_left(&with_columns(&[(10, 15), (80, 85)])), 60);
+}
+
+#[test]
+fn auto_anchor_follows_edges_in_opaque_images() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(100, 10, |x, y| {
+        if x >= 85 && (x + y) % 2 == 0 {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([0, 0, 0])
+        }
+    }));
+    assert_eq!(auto_left(&img), 75);
+    let flat = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 10, Rgb([90, 90, 90])));
+    assert_eq!(auto_left(&flat), 37);
+}
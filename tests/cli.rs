@@ -0,0 +1,824 @@
+//! Runs the command-line tool on fixture directories that are generated on the fly and
+//! compares its output with golden images and reports.
+//!
+//! Golden images are derived from the fixtures in code. Golden reports and manifests are
+//! kept in `tests/golden`; set `UPDATE_GOLDEN=1` to rewrite them after an intended change.
+
+mod common;
+
+use common::with_content;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, SystemTime};
+
+/// A fixture image and the area of it the default settings keep.
+struct Fixture {
+    name: &'static str,
+    image: DynamicImage,
+    kept: (u32, u32, u32, u32),
+}
+
+/// A gradient that makes every pixel of the content distinct.
+fn paint(x: u32, y: u32) -> Rgba<u8> {
+    Rgba([
+        (x * 7 % 256) as u8,
+        (y * 11 % 256) as u8,
+        ((x + y) % 256) as u8,
+        255,
+    ])
+}
+
+fn fixtures() -> Vec<Fixture> {
+    vec![
+        // 20x32 の内容は 2:5..=5:2 に収まるので切り抜くだけ
+        Fixture {
+            name: "sprite.png",
+            image: with_content(64, 48, (10, 8, 30, 40), paint),
+            kept: (10, 8, 30, 40),
+        },
+        // 120x10 の帯は横長すぎるので中央の 25x10 が残る
+        Fixture {
+            name: "banner.png",
+            image: with_content(140, 30, (10, 5, 130, 15), paint),
+            kept: (57, 5, 82, 15),
+        },
+        // 全面透明の画像はそのまま残す
+        Fixture {
+            name: "empty.png",
+            image: with_content(16, 16, (0, 0, 0, 0), paint),
+            kept: (0, 0, 16, 16),
+        },
+        // アルファのない画像はトリムしない
+        Fixture {
+            name: "opaque.png",
+            image: DynamicImage::ImageRgb8(RgbImage::from_fn(30, 20, |x, y| {
+                let Rgba([r, g, b, _]) = paint(x, y);
+                Rgb([r, g, b])
+            })),
+            kept: (0, 0, 30, 20),
+        },
+    ]
+}
+
+fn write_fixtures(dir: &Path) -> Vec<Fixture> {
+    fs::create_dir_all(dir).unwrap();
+    let fixtures = fixtures();
+    for fixture in &fixtures {
+        fixture.image.save(dir.join(fixture.name)).unwrap();
+    }
+    fixtures
+}
+
+/// The image expected from cropping `fixture`, as RGBA.
+fn golden_image(fixture: &Fixture) -> RgbaImage {
+    let (left, top, right, bottom) = fixture.kept;
+    fixture
+        .image
+        .crop_imm(left, top, right - left, bottom - top)
+        .to_rgba8()
+}
+
+fn assert_image_eq(actual: &DynamicImage, expected: &RgbaImage, what: &str) {
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "size of {}",
+        what
+    );
+    assert!(actual.to_rgba8() == *expected, "pixels of {} differ", what);
+}
+
+/// Compares `actual` with the golden JSON file `name`, or rewrites it with `UPDATE_GOLDEN=1`.
+fn assert_golden_json(actual: &Value, name: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let text = serde_json::to_string_pretty(actual).unwrap() + "\n";
+        fs::write(&path, text).unwrap();
+        return;
+    }
+    let expected: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(
+        actual, &expected,
+        "{} differs from the golden file; rerun with UPDATE_GOLDEN=1 if the change is intended",
+        name
+    );
+}
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// Runs the tool in `dir` with `args`.
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_image-cropper"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn directory_output_matches_golden_images_and_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixtures = write_fixtures(&dir.path().join("in"));
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--report",
+            "report.json",
+            "--manifest",
+            "out/manifest.json",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("4 written"), "{}", stderr(&output));
+
+    for fixture in &fixtures {
+        let stem = fixture.name.trim_end_matches(".png");
+        let path = dir.path().join(format!("out/{}_cropped.png", stem));
+        let actual = image::open(&path).unwrap();
+        assert_image_eq(&actual, &golden_image(fixture), fixture.name);
+    }
+
+    // 実行時間は毎回変わるので比較から外す
+    let mut report = read_json(&dir.path().join("report.json"));
+    for entry in report.as_array_mut().unwrap() {
+        entry["duration_ms"] = Value::from(0);
+    }
+    assert_golden_json(&report, "report.json");
+
+    let mut manifest = read_json(&dir.path().join("out/manifest.json"));
+    manifest["meta"]["version"] = Value::from("");
+    assert_golden_json(&manifest, "manifest.json");
+}
+
+/// A white `width` x `height` image with a solid `color` block inside `left..right`,
+/// `top..bottom`.
+fn card(
+    width: u32,
+    height: u32,
+    (left, top, right, bottom): (u32, u32, u32, u32),
+    color: Rgb<u8>,
+) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        if (left..right).contains(&x) && (top..bottom).contains(&y) {
+            color
+        } else {
+            Rgb([255, 255, 255])
+        }
+    })
+}
+
+#[test]
+fn background_trim_with_margin_and_padding_matches_golden_images_and_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    let label = card(50, 40, (10, 12, 30, 20), Rgb([200, 30, 30]));
+    let strip = card(80, 30, (5, 10, 75, 14), Rgb([30, 30, 200]));
+    label.save(input.join("label.png")).unwrap();
+    strip.save(input.join("strip.png")).unwrap();
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--trim-mode",
+            "background",
+            "--margin",
+            "2",
+            "--aspect-mode",
+            "pad",
+            "--pad-fill",
+            "#000000",
+            "--report",
+            "report.json",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // 24x12 は範囲内なので余白付きで切り抜くだけ
+    let expected = image::imageops::crop_imm(&label, 8, 10, 24, 12).to_image();
+    let actual = image::open(dir.path().join("out/label_cropped.png")).unwrap();
+    assert_image_eq(
+        &actual,
+        &DynamicImage::ImageRgb8(expected).to_rgba8(),
+        "label",
+    );
+
+    // 74x8 は横長すぎるので 5:2 になるまで上下に黒い余白を 11 行ずつ足す
+    let kept = image::imageops::crop_imm(&strip, 3, 8, 74, 8).to_image();
+    let mut expected = RgbImage::from_pixel(74, 30, Rgb([0, 0, 0]));
+    image::imageops::replace(&mut expected, &kept, 0, 11);
+    let actual = image::open(dir.path().join("out/strip_cropped.png")).unwrap();
+    assert_image_eq(
+        &actual,
+        &DynamicImage::ImageRgb8(expected).to_rgba8(),
+        "strip",
+    );
+
+    let mut report = read_json(&dir.path().join("report.json"));
+    for entry in report.as_array_mut().unwrap() {
+        entry["duration_ms"] = Value::from(0);
+    }
+    assert_golden_json(&report, "report_background_pad.json");
+}
+
+#[test]
+fn in_place_with_backup_keeps_the_original_next_to_the_cropped_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = write_fixtures(&input);
+    let original = fs::read(input.join("sprite.png")).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(input.join("sprite.png"), fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let output = run(dir.path(), &["-i", "in", "--in-place", "--backup"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("4 written"), "{}", stderr(&output));
+    assert!(!input.join("output").exists());
+
+    for fixture in &fixtures {
+        let actual = image::open(input.join(fixture.name)).unwrap();
+        assert_image_eq(&actual, &golden_image(fixture), fixture.name);
+    }
+    let backup = input.join("sprite.png.bak");
+    assert_eq!(fs::read(&backup).unwrap(), original);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(input.join("sprite.png"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn dry_run_reports_without_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    write_fixtures(&dir.path().join("in"));
+
+    let output = run(
+        dir.path(),
+        &["-i", "in", "-o", "out", "--dry-run", "--report", "-"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!dir.path().join("out").exists());
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let entries = report.as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|entry| entry["status"] == "dry-run"));
+}
+
+#[test]
+fn failed_files_set_the_exit_code_without_stopping_the_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_fixtures(&input);
+    fs::write(input.join("broken.png"), b"not a png").unwrap();
+
+    let output = run(dir.path(), &["-i", "in", "-o", "out"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("broken.png"), "{}", stderr);
+    assert!(stderr.contains("4 written"), "{}", stderr);
+    assert!(stderr.contains("1 failed"), "{}", stderr);
+    assert!(dir.path().join("out/sprite_cropped.png").exists());
+}
+
+#[test]
+fn missing_input_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run(dir.path(), &["-i", "missing.png"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("missing.png"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn output_file_takes_its_format_from_the_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixtures = write_fixtures(&dir.path().join("in"));
+
+    let output = run(
+        dir.path(),
+        &["-i", "in/sprite.png", "--output-file", "sprite.webp"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let bytes = fs::read(dir.path().join("sprite.webp")).unwrap();
+    assert_eq!(image::guess_format(&bytes).unwrap(), ImageFormat::WebP);
+    let actual = image::load_from_memory(&bytes).unwrap();
+    assert_image_eq(&actual, &golden_image(&fixtures[0]), "sprite.webp");
+}
+
+#[test]
+fn stdin_is_cropped_to_stdout() {
+    let fixture = &fixtures()[1];
+    let mut input = Vec::new();
+    fixture
+        .image
+        .write_to(&mut std::io::Cursor::new(&mut input), ImageFormat::Png)
+        .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_image-cropper"))
+        .args(["-i", "-", "-o", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let actual = image::load_from_memory(&output.stdout).unwrap();
+    assert_image_eq(&actual, &golden_image(fixture), "stdout");
+}
+
+#[test]
+fn outputs_that_resolve_to_the_input_are_refused_without_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    write_fixtures(&dir.path().join("in"));
+    let original = fs::read(dir.path().join("in/sprite.png")).unwrap();
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in/sprite.png",
+            "-o",
+            "in",
+            "--name-template",
+            "{stem}.{ext}",
+            "--format",
+            "same",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("is the input file"),
+        "{}",
+        stderr(&output)
+    );
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in/sprite.png",
+            "--output-file",
+            "in/../in/sprite.png",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        fs::read(dir.path().join("in/sprite.png")).unwrap(),
+        original
+    );
+
+    // 単一ファイルの既定の出力先はディレクトリのときと同じく隣の output
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in/sprite.png",
+            "--name-template",
+            "{stem}.{ext}",
+            "--format",
+            "same",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join("in/output/sprite.png").is_file());
+
+    let output = run(dir.path(), &["-i", "in/sprite.png", "--in-place"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_ne!(
+        fs::read(dir.path().join("in/sprite.png")).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn streaming_rejects_options_that_need_per_file_results() {
+    let dir = tempfile::tempdir().unwrap();
+    write_fixtures(&dir.path().join("in"));
+
+    for extra in [
+        &["--report", "report.json"][..],
+        &["--manifest", "manifest.json"],
+        &["--sidecar", "json"],
+        &["--max-area-loss", "50"],
+    ] {
+        let mut args = vec!["-i", "in/sprite.png", "-o", "-"];
+        args.extend_from_slice(extra);
+        let output = run(dir.path(), &args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", extra);
+        assert!(output.stdout.is_empty(), "{:?}", extra);
+        assert!(stderr(&output).contains(extra[0]), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn grouped_frames_share_one_rectangle() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    // 内容が右下へ動くフレーム。3 枚の和集合は (4, 6)..(22, 30)
+    let frames = [(4, 6, 12, 20), (10, 10, 18, 26), (14, 14, 22, 30)];
+    for (i, &bounds) in frames.iter().enumerate() {
+        with_content(40, 40, bounds, paint)
+            .save(input.join(format!("walk_{:03}.png", i)))
+            .unwrap();
+    }
+
+    let output = run(
+        dir.path(),
+        &["-i", "in", "-o", "out", "--group-by", "sequence"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    for (i, &bounds) in frames.iter().enumerate() {
+        let expected = Fixture {
+            name: "walk",
+            image: with_content(40, 40, bounds, paint),
+            kept: (4, 6, 22, 30),
+        };
+        let path = dir.path().join(format!("out/walk_{:03}_cropped.png", i));
+        assert_image_eq(
+            &image::open(&path).unwrap(),
+            &golden_image(&expected),
+            "frame",
+        );
+    }
+}
+
+#[test]
+fn outputs_kept_by_the_overwrite_policy_are_skipped_without_decoding_the_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = write_fixtures(&input);
+    let output = run(dir.path(), &["-i", "in", "-o", "out"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let written = fs::read(dir.path().join("out/sprite_cropped.png")).unwrap();
+
+    // 入力を壊しても、既存の出力を残すならデコードしないので失敗しない
+    for fixture in &fixtures {
+        let path = input.join(fixture.name);
+        fs::write(&path, b"not a png").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+    }
+    for policy in ["never", "if-newer"] {
+        let output = run(
+            dir.path(),
+            &["-i", "in", "-o", "out", "--overwrite", policy],
+        );
+        assert!(output.status.success(), "{}: {}", policy, stderr(&output));
+        assert!(stderr(&output).contains("4 skipped"), "{}", stderr(&output));
+    }
+    assert_eq!(
+        fs::read(dir.path().join("out/sprite_cropped.png")).unwrap(),
+        written
+    );
+
+    // マニフェストには切り抜き範囲が要るので、その場合はデコードする
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--overwrite",
+            "never",
+            "--manifest",
+            "m.json",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("4 failed"), "{}", stderr(&output));
+}
+
+#[test]
+fn sidecars_are_written_next_to_each_image_under_its_full_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = write_fixtures(&input);
+    // 拡張子だけ違う入力でもサイドカーは別々になる
+    fixtures[1].image.save(input.join("sprite.bmp")).unwrap();
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in/sprite.png",
+            "-i",
+            "in/sprite.bmp",
+            "-o",
+            "out",
+            "--format",
+            "same",
+            "--sidecar",
+            "json",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("2 written"), "{}", stderr(&output));
+
+    for (name, fixture) in [
+        ("sprite_cropped.png", &fixtures[0]),
+        ("sprite_cropped.bmp", &fixtures[1]),
+    ] {
+        assert!(dir.path().join("out").join(name).exists(), "{}", name);
+        let sidecar = read_json(&dir.path().join(format!("out/{}.json", name)));
+        let (left, top, right, bottom) = fixture.kept;
+        let (width, height) = fixture.image.dimensions();
+        assert_eq!(
+            sidecar["spriteSourceSize"],
+            serde_json::json!({"x": left, "y": top, "w": right - left, "h": bottom - top}),
+            "{}",
+            name
+        );
+        assert_eq!(
+            sidecar["sourceSize"],
+            serde_json::json!({"w": width, "h": height}),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn inputs_sharing_an_output_path_fail_instead_of_overwriting_each_other() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = write_fixtures(&input);
+    fixtures[0].image.save(input.join("sprite.bmp")).unwrap();
+
+    let output = run(dir.path(), &["-i", "in", "-o", "out"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("would also be written for"), "{}", stderr);
+    assert!(stderr.contains("2 failed"), "{}", stderr);
+    assert!(!dir.path().join("out/sprite_cropped.png").exists());
+    assert!(dir.path().join("out/banner_cropped.png").exists());
+}
+
+#[test]
+fn fail_fast_reports_the_files_it_never_started() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_fixtures(&input);
+    // 名前順で最初に処理されるように壊れたファイルを置く
+    fs::write(input.join("0_broken.png"), b"not a png").unwrap();
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--fail-fast",
+            "-n",
+            "1",
+            "--report",
+            "report.json",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("1 failed, 4 not attempted"), "{}", stderr);
+
+    let report = read_json(&dir.path().join("report.json"));
+    let statuses: Vec<_> = report
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            "failed",
+            "not-attempted",
+            "not-attempted",
+            "not-attempted",
+            "not-attempted"
+        ]
+    );
+    assert!(!dir.path().join("out").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_images_are_processed() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixtures = write_fixtures(&dir.path().join("real"));
+    let input = dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    std::os::unix::fs::symlink("../real/sprite.png", input.join("link.png")).unwrap();
+    std::os::unix::fs::symlink("../real/missing.png", input.join("broken.png")).unwrap();
+
+    let output = run(dir.path(), &["-i", "in", "-o", "out"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let actual = image::open(dir.path().join("out/link_cropped.png")).unwrap();
+    assert_image_eq(&actual, &golden_image(&fixtures[0]), "link.png");
+}
+
+#[cfg(unix)]
+#[test]
+fn files_from_accepts_paths_that_are_not_utf8() {
+    use std::os::unix::ffi::OsStrExt;
+    let dir = tempfile::tempdir().unwrap();
+    let fixtures = write_fixtures(&dir.path().join("in"));
+    let name = std::ffi::OsStr::from_bytes(b"caf\xe9.png");
+    fs::copy(dir.path().join("in/sprite.png"), dir.path().join(name)).unwrap();
+
+    let mut list = b"in/banner.png\0".to_vec();
+    list.extend_from_slice(name.as_bytes());
+    list.push(0);
+    fs::write(dir.path().join("list"), list).unwrap();
+
+    let output = run(dir.path(), &["--files-from", "list", "-o", "out"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("2 written"), "{}", stderr(&output));
+    let actual = image::open(dir.path().join("out/caf\u{fffd}_cropped.png")).unwrap();
+    assert_image_eq(&actual, &golden_image(&fixtures[0]), "non-UTF-8 input");
+}
+
+#[test]
+fn recursive_mode_mirrors_the_tree_without_reprocessing_its_own_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = fixtures();
+    let layout = [
+        ("sprite.png", &fixtures[0]),
+        ("a/banner.png", &fixtures[1]),
+        ("a/b/opaque.png", &fixtures[3]),
+    ];
+    for (path, fixture) in layout {
+        let path = input.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fixture.image.save(path).unwrap();
+    }
+
+    // 既定の出力先 in/output は入力ツリーの中にあるので、2 回目も元の 3 枚だけを処理する
+    for _ in 0..2 {
+        let output = run(dir.path(), &["-i", "in", "--recursive"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(
+            stderr(&output).contains("3 processed: 3 written"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    for (path, fixture) in layout {
+        let cropped = path.replace(".png", "_cropped.png");
+        let actual = image::open(input.join("output").join(&cropped)).unwrap();
+        assert_image_eq(&actual, &golden_image(fixture), path);
+    }
+    assert!(!input.join("output/output").exists());
+    assert!(!input.join("output/sprite_cropped_cropped.png").exists());
+}
+
+#[test]
+fn include_and_exclude_filter_paths_relative_to_the_input_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = write_fixtures(&input);
+    fixtures[0].image.save(input.join("sprite.bmp")).unwrap();
+    fs::create_dir(input.join("sub")).unwrap();
+    fixtures[1]
+        .image
+        .save(input.join("sub/banner.png"))
+        .unwrap();
+
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--include",
+            "*.png",
+            "--exclude",
+            "b*",
+            "--exclude",
+            "e*",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("2 processed: 2 written"),
+        "{}",
+        stderr(&output)
+    );
+    for (name, written) in [
+        ("sprite_cropped.png", true),
+        ("opaque_cropped.png", true),
+        ("banner_cropped.png", false),
+        ("empty_cropped.png", false),
+    ] {
+        assert_eq!(
+            dir.path().join("out").join(name).exists(),
+            written,
+            "{}",
+            name
+        );
+    }
+
+    // パターンは入力ディレクトリからの相対パスに一致させる
+    let output = run(
+        dir.path(),
+        &[
+            "-i",
+            "in",
+            "-o",
+            "sub-only",
+            "--recursive",
+            "--include",
+            "sub/*",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("1 processed: 1 written"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(dir.path().join("sub-only/sub/banner_cropped.png").is_file());
+}
+
+#[test]
+fn glob_inputs_mirror_and_filter_paths_relative_to_the_pattern_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let fixtures = fixtures();
+    let layout = [
+        ("sprite.png", &fixtures[0]),
+        ("a/banner.png", &fixtures[1]),
+        ("a/b/opaque.png", &fixtures[3]),
+        ("a/b/empty.png", &fixtures[2]),
+    ];
+    for (path, fixture) in layout {
+        let path = input.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fixture.image.save(path).unwrap();
+    }
+
+    // フィルタはパターンの先頭の in からの相対パスに一致させ、既定の出力先 in/output は
+    // パターンに一致しても処理しない
+    for _ in 0..2 {
+        let output = run(dir.path(), &["-i", "in/**/*.png", "--exclude", "a/b/e*"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(
+            stderr(&output).contains("3 processed: 3 written"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    for (path, fixture) in &layout[..3] {
+        let cropped = path.replace(".png", "_cropped.png");
+        let actual = image::open(input.join("output").join(&cropped)).unwrap();
+        assert_image_eq(&actual, &golden_image(fixture), path);
+    }
+    assert!(!input.join("output/a/b/empty_cropped.png").exists());
+}
@@ -0,0 +1,35 @@
+//! Fixture images shared by the integration tests.
+
+// テストのクレートごとに使う関数が違うので、使わないものがあっても警告しない
+#![allow(dead_code)]
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// The colour of content pixels that do not need to be told apart.
+pub const OPAQUE: Rgba<u8> = Rgba([200, 100, 50, 255]);
+
+/// A transparent `width` x `height` image with content coloured by `paint` inside
+/// `left..right`, `top..bottom`.
+pub fn with_content(
+    width: u32,
+    height: u32,
+    (left, top, right, bottom): (u32, u32, u32, u32),
+    paint: impl Fn(u32, u32) -> Rgba<u8>,
+) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if (left..right).contains(&x) && (top..bottom).contains(&y) {
+            paint(x, y)
+        } else {
+            Rgba([0, 0, 0, 0])
+        }
+    }))
+}
+
+/// A transparent `width` x `height` image with [`OPAQUE`] pixels at `points`.
+pub fn with_pixels(width: u32, height: u32, points: &[(u32, u32)]) -> DynamicImage {
+    let mut img = RgbaImage::new(width, height);
+    for &(x, y) in points {
+        img.put_pixel(x, y, OPAQUE);
+    }
+    DynamicImage::ImageRgba8(img)
+}
@@ -0,0 +1,79 @@
+use image::{DynamicImage, ImageFormat, Luma, LumaA, Rgb, Rgba};
+use image_cropper::{encode_image, EncoderOptions};
+
+/// A small image of every color type `image` decodes to.
+fn color_types() -> Vec<DynamicImage> {
+    let (w, h) = (5, 3);
+    vec![
+        DynamicImage::ImageLuma8(image::ImageBuffer::from_pixel(w, h, Luma([90]))),
+        DynamicImage::ImageLumaA8(image::ImageBuffer::from_pixel(w, h, LumaA([90, 128]))),
+        DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(w, h, Rgb([1, 2, 3]))),
+        DynamicImage::ImageRgba8(image::ImageBuffer::from_pixel(w, h, Rgba([1, 2, 3, 4]))),
+        DynamicImage::ImageLuma16(image::ImageBuffer::from_pixel(w, h, Luma([900]))),
+        DynamicImage::ImageLumaA16(image::ImageBuffer::from_pixel(w, h, LumaA([900, 5]))),
+        DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(w, h, Rgb([1, 2, 3]))),
+        DynamicImage::ImageRgba16(image::ImageBuffer::from_pixel(w, h, Rgba([1, 2, 3, 4]))),
+        DynamicImage::ImageRgb32F(image::ImageBuffer::from_pixel(w, h, Rgb([0.1, 0.2, 0.3]))),
+        DynamicImage::ImageRgba32F(image::ImageBuffer::from_pixel(
+            w,
+            h,
+            Rgba([0.1, 0.2, 0.3, 0.4]),
+        )),
+    ]
+}
+
+#[test]
+fn every_writable_format_accepts_every_color_type() {
+    let options = EncoderOptions::default();
+    let mut failures = Vec::new();
+    for format in ImageFormat::all().filter(ImageFormat::writing_enabled) {
+        for img in color_types() {
+            if let Err(e) = encode_image(&img, format, &options) {
+                failures.push(format!("{:?} from {:?}: {}", format, img.color(), e));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn encoded_images_decode_to_the_same_size() {
+    let options = EncoderOptions::default();
+    for format in ImageFormat::all().filter(|f| f.writing_enabled() && f.reading_enabled()) {
+        if format == ImageFormat::Avif {
+            continue;
+        }
+        for img in color_types() {
+            let bytes = encode_image(&img, format, &options).unwrap();
+            let decoded = image::load_from_memory_with_format(&bytes, format).unwrap();
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (5, 3),
+                "{:?} from {:?}",
+                format,
+                img.color()
+            );
+        }
+    }
+}
+
+#[cfg(feature = "webp-lossy")]
+#[test]
+fn lossy_webp_failures_are_errors_instead_of_panics() {
+    let lossy = EncoderOptions {
+        webp_lossless: false,
+        ..EncoderOptions::default()
+    };
+    // WebP の辺は 16383 ピクセルまで
+    let wide = DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(16400, 1, Rgb([1, 2, 3])));
+    let err = encode_image(&wide, ImageFormat::WebP, &lossy).unwrap_err();
+    assert!(err.to_string().contains("BAD_DIMENSION"), "{}", err);
+
+    let small = &color_types()[2];
+    let invalid = EncoderOptions {
+        webp_quality: 500.0,
+        ..lossy
+    };
+    assert!(encode_image(small, ImageFormat::WebP, &invalid).is_err());
+    assert!(encode_image(small, ImageFormat::WebP, &lossy).is_ok());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::{with_content, OPAQUE};
+use image::GenericImageView;
+use image_cropper::{AspectMode, CropBox, Cropper, Error, Length, Margin, MarginMode};
+
+fn margin(s: &str) -> Margin {
+    s.parse().unwrap()
+}
+
+fn sides(margin: Margin) -> [Length; 4] {
+    [margin.top, margin.right, margin.bottom, margin.left]
+}
+
+#[test]
+fn margins_parse_like_css() {
+    use Length::{Percent, Pixels};
+    assert_eq!(margin("8"), Margin::uniform(Pixels(8)));
+    assert_eq!(margin("8px"), Margin::uniform(Pixels(8)));
+    assert_eq!(margin("5%"), Margin::uniform(Percent(5.0)));
+    assert_eq!(
+        sides(margin("4,8")),
+        [Pixels(4), Pixels(8), Pixels(4), Pixels(8)]
+    );
+    assert_eq!(
+        sides(margin("1, 2%, 3")),
+        [Pixels(1), Percent(2.0), Pixels(3), Percent(2.0)]
+    );
+    assert_eq!(
+        sides(margin("1,2,3,4")),
+        [Pixels(1), Pixels(2), Pixels(3), Pixels(4)]
+    );
+}
+
+#[test]
+fn invalid_margins_are_rejected() {
+    for s in ["", "x", "-1", "-5%", "inf%", "1,2,3,4,5", "1,,2"] {
+        assert!(s.parse::<Margin>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn percentages_are_relative_to_the_content_side() {
+    let content = CropBox {
+        left: 10,
+        top: 10,
+        right: 30,
+        bottom: 20,
+    };
+    // 幅 20 の 10% は 2、高さ 10 の 10% は 1
+    let expanded = margin("10%").expand(content, 100, 100, MarginMode::Clamp);
+    assert_eq!(
+        expanded,
+        CropBox {
+            left: 8,
+            top: 9,
+            right: 32,
+            bottom: 21,
+        }
+    );
+}
+
+#[test]
+fn clamp_keeps_the_margin_inside_the_image_and_extend_does_not() {
+    let content = CropBox {
+        left: 2,
+        top: 10,
+        right: 30,
+        bottom: 28,
+    };
+    let margin = margin("5");
+    assert_eq!(
+        margin.expand(content, 40, 30, MarginMode::Clamp),
+        CropBox {
+            left: 0,
+            top: 5,
+            right: 35,
+            bottom: 30,
+        }
+    );
+    assert_eq!(
+        margin.expand(content, 40, 30, MarginMode::Extend),
+        CropBox {
+            left: -3,
+            top: 5,
+            right: 35,
+            bottom: 33,
+        }
+    );
+}
+
+#[test]
+fn extended_margins_put_the_source_offset_before_the_image() {
+    let img = with_content(20, 20, (0, 0, 4, 4), |_, _| OPAQUE);
+    let cropper = Cropper::builder()
+        .margin(margin("3"))
+        .margin_mode(MarginMode::Extend)
+        .build();
+    let (cropped, info) = cropper.crop_with_info(&img).unwrap();
+
+    assert_eq!(info.final_size, (10, 10));
+    assert_eq!(info.source_offset(), (-3, -3));
+    // 元画像の (0, 0) は結果の (3, 3) にある
+    assert_eq!(cropped.get_pixel(2, 2)[3], 0);
+    assert_eq!(cropped.get_pixel(3, 3), OPAQUE);
+}
+
+#[test]
+fn padding_puts_the_source_offset_before_the_content() {
+    let img = with_content(60, 20, (10, 5, 50, 15), |_, _| OPAQUE);
+    let cropper = Cropper::builder()
+        .aspect_range(1.0, 1.0)
+        .aspect_mode(AspectMode::Pad)
+        .build();
+    let (cropped, info) = cropper.crop_with_info(&img).unwrap();
+
+    // 40x10 の内容を 40x40 に広げるので上下に 15 ずつ余白が入る
+    assert_eq!(info.final_size, (40, 40));
+    assert_eq!(info.source_offset(), (10, 5 - 15));
+    assert_eq!(cropped.get_pixel(0, 14)[3], 0);
+    assert_eq!(cropped.get_pixel(0, 15), OPAQUE);
+    assert_eq!(cropped.get_pixel(39, 24), OPAQUE);
+    assert_eq!(cropped.get_pixel(39, 25)[3], 0);
+}
+
+#[test]
+fn clamped_margins_and_crops_start_inside_the_image() {
+    let img = with_content(20, 20, (6, 4, 10, 12), |_, _| OPAQUE);
+    let cropper = Cropper::builder().margin(margin("2")).build();
+    let (_, info) = cropper.crop_with_info(&img).unwrap();
+    assert_eq!(info.source_offset(), (4, 2));
+}
+
+#[test]
+fn huge_percentages_saturate_instead_of_wrapping() {
+    assert_eq!(Length::Percent(1e11).resolve(20), u32::MAX);
+    assert_eq!(Length::Percent(50.0).resolve(u32::MAX), 2_147_483_648);
+}
+
+#[test]
+fn extended_margins_too_large_to_allocate_fail() {
+    let img = with_content(20, 20, (6, 4, 10, 12), |_, _| OPAQUE);
+    for margin in ["3000000000", "100000000000%", "20000"] {
+        let cropper = Cropper::builder()
+            .margin(margin.parse().unwrap())
+            .margin_mode(MarginMode::Extend)
+            .build();
+        let err = cropper.crop_with_info(&img).unwrap_err();
+        assert!(
+            matches!(err, Error::CanvasTooLarge(..)),
+            "{}: {}",
+            margin,
+            err
+        );
+    }
+
+    // 画像内に収める場合は余白がいくら大きくても画像全体になるだけ
+    let cropper = Cropper::builder().margin(margin("3000000000")).build();
+    let (cropped, _) = cropper.crop_with_info(&img).unwrap();
+    assert_eq!(cropped.dimensions(), (20, 20));
+}
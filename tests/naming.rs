@@ -0,0 +1,68 @@
+use image_cropper::{NameContext, NameTemplate};
+
+fn context() -> NameContext<'static> {
+    NameContext {
+        stem: "hero",
+        ext: "png",
+        width: 64,
+        height: 7,
+        index: 3,
+        hash: 0x0123_4567_89ab_cdef,
+        parent: "sprites",
+    }
+}
+
+fn render(template: &str) -> String {
+    template.parse::<NameTemplate>().unwrap().render(&context())
+}
+
+#[test]
+fn default_template_appends_cropped_to_the_stem() {
+    assert_eq!(
+        NameTemplate::default().render(&context()),
+        "hero_cropped.png"
+    );
+}
+
+#[test]
+fn every_placeholder_is_substituted() {
+    assert_eq!(
+        render("{parent}/{stem}_{width}x{height}_{index}_{hash}.{ext}"),
+        "sprites/hero_64x7_3_0123456789abcdef.png"
+    );
+}
+
+#[test]
+fn widths_zero_pad_numbers_and_shorten_hashes() {
+    assert_eq!(render("{index:04}"), "0003");
+    assert_eq!(render("{width:1}x{height:3}"), "64x007");
+    assert_eq!(render("{hash:8}"), "01234567");
+    // 16 桁より長い幅はハッシュ全体になる
+    assert_eq!(render("{hash:20}"), "0123456789abcdef");
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(render("{{{stem}}}.{ext}"), "{hero}.png");
+    assert_eq!(render("}}{{"), "}{");
+}
+
+#[test]
+fn display_returns_the_original_template() {
+    let template: NameTemplate = "{stem}-{index:02}.{ext}".parse().unwrap();
+    assert_eq!(template.to_string(), "{stem}-{index:02}.{ext}");
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    for (template, reason) in [
+        ("{name}.png", "unknown placeholder '{name}'"),
+        ("{stem", "unclosed '{'"),
+        ("stem}", "unmatched '}'"),
+        ("{index:x}", "bad width"),
+        ("", "template is empty"),
+    ] {
+        let err = template.parse::<NameTemplate>().unwrap_err().to_string();
+        assert!(err.contains(reason), "{}: {}", template, err);
+    }
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{with_pixels, OPAQUE};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgb, RgbImage, Rgba, RgbaImage};
+use image_cropper::{
+    crop_transparent_edges, detect_background, transparent_bounds, Color, ColorSpace,
+};
+use proptest::prelude::*;
+
+/// Bounds of the pixels with alpha above `threshold`, one pixel at a time.
+fn naive_bounds(img: &RgbaImage, threshold: u8) -> (u32, u32, u32, u32) {
+    let content: Vec<_> = img
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[3] > threshold)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    if content.is_empty() {
+        return (0, 0, img.width(), img.height());
+    }
+    let left = content.iter().map(|&(x, _)| x).min().unwrap();
+    let top = content.iter().map(|&(_, y)| y).min().unwrap();
+    let right = content.iter().map(|&(x, _)| x).max().unwrap() + 1;
+    let bottom = content.iter().map(|&(_, y)| y).max().unwrap() + 1;
+    (left, top, right, bottom)
+}
+
+#[test]
+fn fully_transparent_image_is_kept_whole() {
+    let img = with_pixels(40, 30, &[]);
+    assert_eq!(transparent_bounds(&img, 0), (0, 0, 40, 30));
+    assert_eq!(crop_transparent_edges(&img, 0).dimensions(), (40, 30));
+}
+
+#[test]
+fn empty_image_stays_empty() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::new(0, 0));
+    assert_eq!(crop_transparent_edges(&img, 0).dimensions(), (0, 0));
+}
+
+#[test]
+fn single_opaque_pixel_is_all_that_remains() {
+    let img = with_pixels(40, 30, &[(17, 9)]);
+    assert_eq!(transparent_bounds(&img, 0), (17, 9, 18, 10));
+    let cropped = crop_transparent_edges(&img, 0);
+    assert_eq!(cropped.dimensions(), (1, 1));
+    assert_eq!(cropped.get_pixel(0, 0), OPAQUE);
+}
+
+#[test]
+fn one_by_one_images_are_unchanged() {
+    for img in [with_pixels(1, 1, &[]), with_pixels(1, 1, &[(0, 0)])] {
+        assert_eq!(crop_transparent_edges(&img, 0).dimensions(), (1, 1));
+    }
+}
+
+#[test]
+fn content_touching_every_edge_is_not_cropped() {
+    let img = with_pixels(40, 30, &[(0, 12), (39, 3), (20, 0), (7, 29)]);
+    assert_eq!(transparent_bounds(&img, 0), (0, 0, 40, 30));
+}
+
+#[test]
+fn content_in_the_corners_is_not_cropped() {
+    let img = with_pixels(40, 30, &[(0, 0), (39, 29)]);
+    assert_eq!(transparent_bounds(&img, 0), (0, 0, 40, 30));
+    let img = with_pixels(40, 30, &[(39, 0), (0, 29)]);
+    assert_eq!(transparent_bounds(&img, 0), (0, 0, 40, 30));
+}
+
+#[test]
+fn content_touching_one_edge_keeps_that_edge() {
+    let img = with_pixels(40, 30, &[(0, 10), (5, 20)]);
+    assert_eq!(transparent_bounds(&img, 0), (0, 10, 6, 21));
+    let img = with_pixels(40, 30, &[(30, 29), (35, 14)]);
+    assert_eq!(transparent_bounds(&img, 0), (30, 14, 36, 30));
+}
+
+#[test]
+fn one_pixel_wide_column_is_trimmed_vertically() {
+    let img = with_pixels(1, 50, &[(0, 12), (0, 30)]);
+    let cropped = crop_transparent_edges(&img, 0);
+    assert_eq!(cropped.dimensions(), (1, 19));
+    assert_eq!(cropped.get_pixel(0, 0), OPAQUE);
+    assert_eq!(cropped.get_pixel(0, 18), OPAQUE);
+}
+
+#[test]
+fn one_pixel_high_row_is_trimmed_horizontally() {
+    let img = with_pixels(50, 1, &[(49, 0)]);
+    assert_eq!(transparent_bounds(&img, 0), (49, 0, 50, 1));
+    let img = with_pixels(50, 1, &[(3, 0), (8, 0)]);
+    assert_eq!(crop_transparent_edges(&img, 0).dimensions(), (6, 1));
+}
+
+#[test]
+fn alpha_at_the_threshold_counts_as_transparent() {
+    let mut img = RgbaImage::new(10, 10);
+    img.put_pixel(2, 2, Rgba([0, 0, 0, 40]));
+    img.put_pixel(6, 7, Rgba([0, 0, 0, 41]));
+    let img = DynamicImage::ImageRgba8(img);
+    assert_eq!(transparent_bounds(&img, 40), (6, 7, 7, 8));
+    assert_eq!(transparent_bounds(&img, 39), (2, 2, 7, 8));
+}
+
+#[test]
+fn threshold_scales_to_16_bit_and_float_images() {
+    let mut img16 = ImageBuffer::<Rgba<u16>, _>::new(10, 10);
+    img16.put_pixel(1, 1, Rgba([0, 0, 0, 40 * 257]));
+    img16.put_pixel(4, 5, Rgba([0, 0, 0, 40 * 257 + 1]));
+    let img16 = DynamicImage::ImageRgba16(img16);
+    assert_eq!(transparent_bounds(&img16, 40), (4, 5, 5, 6));
+
+    let mut img32 = ImageBuffer::<Rgba<f32>, _>::new(10, 10);
+    img32.put_pixel(1, 1, Rgba([0.0, 0.0, 0.0, 0.1]));
+    img32.put_pixel(8, 3, Rgba([0.0, 0.0, 0.0, 0.9]));
+    let img32 = DynamicImage::ImageRgba32F(img32);
+    assert_eq!(transparent_bounds(&img32, 128), (8, 3, 9, 4));
+}
+
+#[test]
+fn images_without_alpha_are_kept_whole() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(30, 20, Rgb([0, 0, 0])));
+    assert_eq!(crop_transparent_edges(&img, 255).dimensions(), (30, 20));
+}
+
+/// A 3x3 image whose corners, clockwise from the top left, have the red values in `corners`.
+fn with_corners(corners: [u8; 4]) -> DynamicImage {
+    let [top_left, top_right, bottom_right, bottom_left] = corners;
+    let mut img = RgbImage::from_pixel(3, 3, Rgb([0, 0, 255]));
+    img.put_pixel(0, 0, Rgb([top_left, 0, 0]));
+    img.put_pixel(2, 0, Rgb([top_right, 0, 0]));
+    img.put_pixel(2, 2, Rgb([bottom_right, 0, 0]));
+    img.put_pixel(0, 2, Rgb([bottom_left, 0, 0]));
+    DynamicImage::ImageRgb8(img)
+}
+
+fn background(corners: [u8; 4], tolerance: f32) -> u8 {
+    detect_background(&with_corners(corners), tolerance, ColorSpace::Rgb).r
+}
+
+#[test]
+fn background_is_the_corner_most_others_agree_with() {
+    assert_eq!(background([10, 200, 200, 200], 0.0), 200);
+    assert_eq!(background([10, 200, 80, 198], 5.0), 200);
+}
+
+#[test]
+fn background_ties_go_to_the_top_left_corner_and_then_clockwise() {
+    assert_eq!(background([10, 20, 30, 40], 0.0), 10);
+    assert_eq!(background([10, 200, 200, 10], 0.0), 10);
+    // 右上と左下が 2 票ずつで並ぶので、時計回りで先の右上を選ぶ
+    assert_eq!(background([0, 100, 200, 105], 10.0), 100);
+}
+
+#[test]
+fn empty_images_fall_back_to_white() {
+    let img = DynamicImage::ImageRgb8(RgbImage::new(0, 0));
+    assert_eq!(detect_background(&img, 0.0, ColorSpace::Lab), Color::WHITE);
+}
+
+proptest! {
+    #[test]
+    fn bounds_match_a_pixel_by_pixel_scan(
+        width in 1u32..80,
+        height in 1u32..80,
+        points in prop::collection::vec((0u32..80, 0u32..80, 0u8..=255), 0..6),
+        threshold in 0u8..=255,
+    ) {
+        let mut img = RgbaImage::new(width, height);
+        for (x, y, alpha) in points {
+            img.put_pixel(x % width, y % height, Rgba([255, 255, 255, alpha]));
+        }
+        let expected = naive_bounds(&img, threshold);
+        let img = DynamicImage::ImageRgba8(img);
+        prop_assert_eq!(transparent_bounds(&img, threshold), expected);
+    }
+}
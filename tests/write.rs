@@ -0,0 +1,62 @@
+use image_cropper::OverwritePolicy;
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Creates `path` with a modification time `age` before now.
+fn touch(path: &Path, age: Duration) {
+    let file = File::create(path).unwrap();
+    file.set_modified(SystemTime::now() - age).unwrap();
+}
+
+#[test]
+fn every_policy_allows_a_missing_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    touch(&input, Duration::ZERO);
+    for policy in [
+        OverwritePolicy::Never,
+        OverwritePolicy::Always,
+        OverwritePolicy::IfNewer,
+    ] {
+        assert!(policy.allows(&input, &dir.path().join("out.png")).unwrap());
+    }
+}
+
+#[test]
+fn if_newer_replaces_only_outputs_older_than_the_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, output) = (dir.path().join("in.png"), dir.path().join("out.png"));
+    let policy = OverwritePolicy::IfNewer;
+
+    touch(&input, Duration::from_secs(60));
+    touch(&output, Duration::from_secs(3600));
+    assert!(policy.allows(&input, &output).unwrap());
+
+    touch(&output, Duration::ZERO);
+    assert!(!policy.allows(&input, &output).unwrap());
+    assert!(OverwritePolicy::Always.allows(&input, &output).unwrap());
+    assert!(!OverwritePolicy::Never.allows(&input, &output).unwrap());
+}
+
+#[test]
+fn if_newer_fails_when_the_input_is_missing() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.png");
+    fs::write(&output, b"").unwrap();
+    let missing = dir.path().join("missing.png");
+    assert!(OverwritePolicy::IfNewer.allows(&missing, &output).is_err());
+}
+
+#[test]
+fn policies_parse_case_insensitively() {
+    assert_eq!(
+        "If-Newer".parse::<OverwritePolicy>().unwrap(),
+        OverwritePolicy::IfNewer
+    );
+    assert_eq!(
+        "never".parse::<OverwritePolicy>().unwrap(),
+        OverwritePolicy::Never
+    );
+    assert!("sometimes".parse::<OverwritePolicy>().is_err());
+}